@@ -1,36 +1,49 @@
+pub mod command_emoji;
+pub mod command_heatmap;
+pub mod command_interactions;
+pub mod command_keywords;
+pub mod command_me;
+pub mod command_members;
+pub mod command_names;
+pub mod command_reactions;
+pub mod command_records;
 pub mod command_scores;
+pub mod command_sessions;
 pub mod command_stats;
+pub mod time_range;
 
-use crate::{MetadataStore, TelegramChatId};
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::{MetadataStore, TelegramChatId, TelegramThreadId, TelegramUserId};
 
-pub fn convert_time(command: &str) -> Option<(i64, &str)> {
-    if let Some(param) = command.split_whitespace().next() {
-        let time_str = &command[param.len()..].trim();
-        if let Ok(duration) = humantime::parse_duration(time_str) {
-            if let Some(after) = SystemTime::now().checked_sub(duration) {
-                if let Ok(after_since_epoch) = after.duration_since(UNIX_EPOCH) {
-                    return Some((after_since_epoch.as_secs().try_into().unwrap(), time_str));
-                } else {
-                    log::error!("System time conversion failed for command {}", command);
-                }
-            }
-        }
-    }
-    None
+/// Returns the arguments of a command, that is everything after the first word.
+pub fn arguments(command: &str) -> &str {
+    command
+        .trim()
+        .split_once(char::is_whitespace)
+        .map_or("", |(_, arguments)| arguments.trim())
+}
+
+/// Where and by whom a command was invoked.
+#[derive(Debug, Clone, Copy)]
+pub struct CommandContext {
+    pub chat_id: TelegramChatId,
+    pub user_id: TelegramUserId,
+    /// The forum topic, if invoked in one
+    pub thread_id: Option<TelegramThreadId>,
+    /// Offset from UTC in seconds, for calendar days and hours
+    pub utc_offset: i32,
 }
 
 pub type CommandProcedure =
-    fn(command: &str, chat_id: TelegramChatId, metadata_store: &mut MetadataStore) -> String;
+    fn(command: &str, context: &CommandContext, metadata_store: &mut MetadataStore) -> String;
 
 pub struct CommandInvocation {
     pub procedure: CommandProcedure,
     pub command_string: String,
-    pub chat_id: TelegramChatId,
+    pub context: CommandContext,
 }
 
 impl CommandInvocation {
     pub fn run(&self, metadata_store: &mut MetadataStore) -> String {
-        (self.procedure)(&self.command_string, self.chat_id, metadata_store)
+        (self.procedure)(&self.command_string, &self.context, metadata_store)
     }
 }
@@ -0,0 +1,60 @@
+use super::CommandContext;
+use crate::{MetadataStore, TelegramUserId};
+use std::collections::HashMap;
+
+const TOP_LIST_LENGTH: usize = 10;
+
+/// Sums per-user counts and sorts them by count, ties alphabetically.
+fn top<'a>(user_counts: &[(TelegramUserId, &'a HashMap<String, u64>)]) -> Vec<(&'a str, u64)> {
+    let mut totals: HashMap<&str, u64> = HashMap::new();
+    for (_, counts) in user_counts {
+        for (key, count) in counts.iter() {
+            *totals.entry(key).or_default() += count;
+        }
+    }
+    let mut totals: Vec<(&str, u64)> = totals.into_iter().collect();
+    totals.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    totals
+}
+
+pub fn render(
+    _command: &str,
+    context: &CommandContext,
+    metadata_store: &mut MetadataStore,
+) -> String {
+    let mut emoji_counts = metadata_store.get_emoji_counts(context.chat_id);
+    let sticker_set_counts = metadata_store.get_sticker_set_counts(context.chat_id);
+    if emoji_counts.is_empty() && sticker_set_counts.is_empty() {
+        return "Ei emojeita eikä tarroja.".into();
+    }
+
+    let mut response = vec![String::from("Suosituimmat emojit:\n")];
+    for (emoji, count) in top(&emoji_counts).into_iter().take(TOP_LIST_LENGTH) {
+        response.push(format!("{} {}\n", emoji, count));
+    }
+
+    let sticker_sets = top(&sticker_set_counts);
+    if !sticker_sets.is_empty() {
+        response.push("\nSuosituimmat tarrasarjat:\n".into());
+        for (set_name, count) in sticker_sets.into_iter().take(TOP_LIST_LENGTH) {
+            response.push(format!("{}: {}\n", set_name, count));
+        }
+    }
+
+    emoji_counts.sort_unstable_by_key(|(user, counts)| {
+        (std::cmp::Reverse(counts.values().sum::<u64>()), *user)
+    });
+    response.push("\nLempiemojit:\n".into());
+    for (user, counts) in emoji_counts {
+        if let Some((emoji, count)) = counts.iter().max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0))) {
+            response.push(format!(
+                "{}: {} {}\n",
+                metadata_store.get_user_name_or_id(context.chat_id, user),
+                emoji,
+                count
+            ));
+        }
+    }
+
+    response.concat()
+}
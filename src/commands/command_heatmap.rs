@@ -0,0 +1,179 @@
+use super::{
+    time_range::{self, TimeRange, WEEKDAYS},
+    CommandContext,
+};
+use crate::MetadataStore;
+
+const LEVELS: [&str; 5] = ["⬜", "🟨", "🟧", "🟥", "🟪"];
+
+#[cfg(feature = "png")]
+const USAGE: &str = "Käyttöohje: /lämpökartta [kuva] [@käyttäjä] [aika]";
+#[cfg(not(feature = "png"))]
+const USAGE: &str = "Käyttöohje: /lämpökartta [@käyttäjä] [aika]";
+
+/// Message counts by weekday (0 for monday) and hour.
+pub struct Heatmap {
+    pub title: String,
+    pub counts: [[usize; 24]; 7],
+}
+
+impl Heatmap {
+    pub fn max(&self) -> usize {
+        self.counts.iter().flatten().copied().max().unwrap_or(0)
+    }
+}
+
+/// Builds a heatmap of the chat or the `@user` given in `arguments`.
+pub fn heatmap(
+    arguments: &str,
+    context: &CommandContext,
+    metadata_store: &MetadataStore,
+) -> Result<Heatmap, String> {
+    let (user, arguments) = match arguments.split_once(char::is_whitespace) {
+        Some((user, arguments)) if user.starts_with('@') => (Some(user), arguments.trim()),
+        _ if arguments.starts_with('@') => (Some(arguments), ""),
+        _ => (None, arguments),
+    };
+    let time_range = match time_range::parse(arguments, context.utc_offset) {
+        Some(time_range) => time_range,
+        None if arguments.is_empty() => TimeRange::all(),
+        None => {
+            return Err(format!(
+                "Tuntematon aika {}.\n\n{}\n{}",
+                arguments,
+                USAGE,
+                time_range::USAGE
+            ))
+        }
+    };
+
+    let mut counts = [[0; 24]; 7];
+    let mut count = |timestamp: i64| {
+        if time_range.contains(timestamp) {
+            let weekday = time_range::local_weekday(timestamp, context.utc_offset);
+            counts[weekday][time_range::local_hour(timestamp, context.utc_offset)] += 1;
+        }
+    };
+
+    let title = match user {
+        Some(username) => {
+            let user_id = metadata_store
+                .find_user_by_username(username)
+                .ok_or_else(|| format!("Käyttäjää {} ei tunneta.", username))?;
+            metadata_store
+                .get_user_timestamps(context.chat_id, user_id)
+                .iter()
+                .for_each(|t| count(*t));
+            format!(
+                "Aktiivisuus {} ({}, {})",
+                metadata_store.get_user_name_or_id(context.chat_id, user_id),
+                time_range.label,
+                time_range::format_utc_offset(context.utc_offset)
+            )
+        }
+        None => {
+            metadata_store
+                .get_chat_timestamps(context.chat_id)
+                .for_each(count);
+            format!(
+                "Aktiivisuus ({}, {})",
+                time_range.label,
+                time_range::format_utc_offset(context.utc_offset)
+            )
+        }
+    };
+
+    Ok(Heatmap { title, counts })
+}
+
+/// Skips the "kuva" argument of an image request.
+fn without_image_argument(arguments: &str) -> &str {
+    match arguments.strip_prefix("kuva") {
+        Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => rest.trim(),
+        _ => arguments,
+    }
+}
+
+/// Renders the heatmap as text, also when an image was asked for but could not be sent.
+pub fn render(
+    command: &str,
+    context: &CommandContext,
+    metadata_store: &mut MetadataStore,
+) -> String {
+    let arguments = without_image_argument(super::arguments(command));
+    let heatmap = match heatmap(arguments, context, metadata_store) {
+        Ok(heatmap) => heatmap,
+        Err(text) => return text,
+    };
+    let max = heatmap.max();
+    if max == 0 {
+        return format!("{}: ei viestejä.", heatmap.title);
+    }
+
+    let mut response = vec![format!("{}, tunnit 0-23:\n\n", heatmap.title)];
+    for (weekday, hours) in heatmap.counts.iter().enumerate() {
+        response.push(format!("{} ", &WEEKDAYS[weekday][..2]));
+        for count in hours {
+            let level = (count * (LEVELS.len() - 1)).div_ceil(max);
+            response.push(LEVELS[level].into());
+        }
+        response.push("\n".into());
+    }
+    response.push(format!(
+        "\n{} 0 … {} {}",
+        LEVELS[0],
+        LEVELS[LEVELS.len() - 1],
+        max
+    ));
+
+    response.concat()
+}
+
+/// Renders the heatmap as a PNG image, or an error message.
+#[cfg(feature = "png")]
+pub fn render_png(
+    command: &str,
+    context: &CommandContext,
+    metadata_store: &MetadataStore,
+) -> Result<(Vec<u8>, String), String> {
+    const CELL: usize = 24;
+    const GAP: usize = 2;
+    const WIDTH: usize = 24 * (CELL + GAP) + GAP;
+    const HEIGHT: usize = 7 * (CELL + GAP) + GAP;
+
+    let arguments = without_image_argument(super::arguments(command));
+    let heatmap = heatmap(arguments, context, metadata_store)?;
+    let max = heatmap.max().max(1);
+
+    let mut pixels = vec![255u8; WIDTH * HEIGHT * 3];
+    for (weekday, hours) in heatmap.counts.iter().enumerate() {
+        for (hour, count) in hours.iter().enumerate() {
+            // From light grey to dark red
+            let t = *count as f32 / max as f32;
+            let color = [
+                (235.0 - t * 75.0) as u8,
+                (237.0 - t * 237.0) as u8,
+                (240.0 - t * 210.0) as u8,
+            ];
+            for y in 0..CELL {
+                for x in 0..CELL {
+                    let px = GAP + hour * (CELL + GAP) + x;
+                    let py = GAP + weekday * (CELL + GAP) + y;
+                    let i = (py * WIDTH + px) * 3;
+                    pixels[i..i + 3].copy_from_slice(&color);
+                }
+            }
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, WIDTH as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(|e| format!("Kuvan luonti epäonnistui: {}", e))?;
+
+    Ok((png, heatmap.title))
+}
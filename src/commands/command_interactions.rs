@@ -0,0 +1,92 @@
+use super::CommandContext;
+use crate::{metadata_store::Interactions, MetadataStore, TelegramChatId, TelegramUserId};
+use std::collections::HashMap;
+
+const PAIR_LIST_LENGTH: usize = 15;
+
+pub fn render(
+    _command: &str,
+    context: &CommandContext,
+    metadata_store: &mut MetadataStore,
+) -> String {
+    // Combine both directions of each pair, the lower user id first
+    let mut pairs: HashMap<(TelegramUserId, TelegramUserId), (u64, u64)> = HashMap::new();
+    for (from, to, interactions) in metadata_store.get_interactions(context.chat_id) {
+        if from < to {
+            pairs.entry((from, to)).or_default().0 += interactions.total();
+        } else {
+            pairs.entry((to, from)).or_default().1 += interactions.total();
+        }
+    }
+    if pairs.is_empty() {
+        return "Ei vastauksia tai mainintoja.".into();
+    }
+
+    let mut pairs: Vec<_> = pairs.into_iter().collect();
+    pairs.sort_unstable_by(|a, b| {
+        (b.1 .0 + b.1 .1)
+            .cmp(&(a.1 .0 + a.1 .1))
+            .then(a.0.cmp(&b.0))
+    });
+
+    let mut response = vec![String::from("Vuorovaikutus (vastaukset ja maininnat):\n\n")];
+    for ((a, b), (a_to_b, b_to_a)) in pairs.into_iter().take(PAIR_LIST_LENGTH) {
+        let (a, b) = (
+            metadata_store.get_user_name_or_id(context.chat_id, a),
+            metadata_store.get_user_name_or_id(context.chat_id, b),
+        );
+        response.push(format!(
+            "{} ↔ {}: {} ({} → {}, {} → {})\n",
+            a,
+            b,
+            a_to_b + b_to_a,
+            a_to_b,
+            b_to_a,
+            b,
+            a
+        ));
+    }
+
+    response.concat()
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Renders the chat's reply and mention graph in Graphviz DOT format.
+pub fn render_dot(chat_id: TelegramChatId, metadata_store: &MetadataStore) -> String {
+    let mut interactions: Vec<(TelegramUserId, TelegramUserId, Interactions)> =
+        metadata_store.get_interactions(chat_id);
+    interactions.sort_unstable_by_key(|(from, to, _)| (*from, *to));
+
+    let mut users: Vec<TelegramUserId> = interactions
+        .iter()
+        .flat_map(|(from, to, _)| [*from, *to])
+        .collect();
+    users.sort_unstable();
+    users.dedup();
+
+    let mut dot = vec![format!("digraph \"{}\" {{\n", chat_id)];
+    for user in users {
+        dot.push(format!(
+            "    \"{}\" [label=\"{}\"];\n",
+            user,
+            escape_dot(&metadata_store.get_user_name_or_id(chat_id, user))
+        ));
+    }
+    for (from, to, interactions) in interactions {
+        dot.push(format!(
+            "    \"{}\" -> \"{}\" [weight={}, label=\"{}\", replies={}, mentions={}];\n",
+            from,
+            to,
+            interactions.total(),
+            interactions.total(),
+            interactions.replies,
+            interactions.mentions
+        ));
+    }
+    dot.push("}\n".into());
+
+    dot.concat()
+}
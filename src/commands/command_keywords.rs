@@ -0,0 +1,116 @@
+use crate::{
+    keywords::{Acknowledgement, Keyword, DEFAULT_REACTION},
+    MetadataStore, TelegramChatId,
+};
+
+const USAGE: &str = "Käyttöohje: /avainsana lista | lisää sana | lisää nimi=sana1|sana2[=paino] \
+| poista nimi | kuittaus erikseen|yhdistetty|vastaus|reaktio|hiljainen \
+| viesti {määrä} {sana} lisätty | reaktio emoji";
+
+const MODIFYING_SUBCOMMANDS: [&str; 5] = ["lisää", "poista", "kuittaus", "viesti", "reaktio"];
+
+/// Splits the command into a subcommand and its argument.
+fn parse(command: &str) -> (Option<&str>, &str) {
+    match command.trim().split_once(char::is_whitespace) {
+        Some((_, arguments)) => match arguments.trim().split_once(char::is_whitespace) {
+            Some((subcommand, argument)) => (Some(subcommand), argument.trim()),
+            None => (Some(arguments.trim()), ""),
+        },
+        None => (None, ""),
+    }
+}
+
+/// Tells whether the command makes changes, so that admin status is needed.
+pub fn is_modification(command: &str) -> bool {
+    parse(command)
+        .0
+        .is_some_and(|subcommand| MODIFYING_SUBCOMMANDS.contains(&subcommand))
+}
+
+/// Lists or modifies the chat's keywords. Modifications are only allowed for chat admins.
+/// Returns the response text and whether the keyword list was changed.
+pub fn run(
+    command: &str,
+    chat_id: TelegramChatId,
+    metadata_store: &mut MetadataStore,
+    default_keywords: &[Keyword],
+    is_admin: bool,
+) -> Result<(String, bool), crate::metadata_store::Error> {
+    let mut keywords = metadata_store
+        .get_chat_keywords(chat_id)
+        .unwrap_or(default_keywords)
+        .to_vec();
+
+    let (subcommand, argument) = parse(command);
+    let mut settings = metadata_store.get_chat_settings(chat_id);
+
+    match (subcommand, argument) {
+        (Some("lista"), _) => {
+            if keywords.is_empty() {
+                return Ok(("Ei avainsanoja.".into(), false));
+            }
+            let mut response = vec![String::from("Avainsanat:\n\n")];
+            for keyword in keywords {
+                response.push(format!("{}\n", keyword));
+            }
+            response.push(format!("\nKuittaus: {}", settings.keyword_acknowledgement));
+            Ok((response.concat(), false))
+        }
+        (Some(subcommand), _) if MODIFYING_SUBCOMMANDS.contains(&subcommand) && !is_admin => {
+            Ok(("Vain ylläpitäjät voivat muokata avainsanoja.".into(), false))
+        }
+        (Some("lisää"), definition) if !definition.is_empty() => {
+            let keyword: Keyword = match definition.parse() {
+                Ok(keyword) => keyword,
+                Err(e) => return Ok((format!("Virheellinen avainsana: {}", e), false)),
+            };
+            let response = format!("Avainsana {} lisätty.", keyword);
+            keywords.retain(|k| k.name != keyword.name);
+            keywords.push(keyword);
+            metadata_store.set_chat_keywords(chat_id, keywords)?;
+            Ok((response, true))
+        }
+        (Some("poista"), name) if !name.is_empty() => {
+            let count = keywords.len();
+            keywords.retain(|k| k.name != name);
+            if keywords.len() == count {
+                return Ok((format!("Avainsanaa {} ei löytynyt.", name), false));
+            }
+            metadata_store.set_chat_keywords(chat_id, keywords)?;
+            Ok((format!("Avainsana {} poistettu.", name), true))
+        }
+        (Some("kuittaus"), mode) if !mode.is_empty() => {
+            settings.keyword_acknowledgement = match mode.parse::<Acknowledgement>() {
+                Ok(mode) => mode,
+                Err(e) => return Ok((format!("{}", e), false)),
+            };
+            let response = format!("Kuittaus: {}", settings.keyword_acknowledgement);
+            metadata_store.set_chat_settings(chat_id, settings)?;
+            Ok((response, false))
+        }
+        (Some("viesti"), template) => {
+            let response = if template.is_empty() {
+                settings.keyword_template = None;
+                "Kuittausviesti palautettu oletukseksi.".into()
+            } else {
+                settings.keyword_template = Some(template.into());
+                format!("Kuittausviesti: {}", template)
+            };
+            metadata_store.set_chat_settings(chat_id, settings)?;
+            Ok((response, false))
+        }
+        (Some("reaktio"), emoji) => {
+            settings.keyword_reaction = Some(emoji).filter(|e| !e.is_empty()).map(String::from);
+            let response = format!(
+                "Reaktio: {}",
+                settings
+                    .keyword_reaction
+                    .as_deref()
+                    .unwrap_or(DEFAULT_REACTION)
+            );
+            metadata_store.set_chat_settings(chat_id, settings)?;
+            Ok((response, false))
+        }
+        _ => Ok((USAGE.into(), false)),
+    }
+}
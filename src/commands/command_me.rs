@@ -0,0 +1,127 @@
+use super::{
+    time_range::{self, TimeRange},
+    CommandContext,
+};
+use crate::{records::Streak, MetadataStore};
+use chrono::{TimeZone, Utc};
+
+/// Returns the index of the largest count, preferring the first one.
+fn busiest(counts: &[usize]) -> usize {
+    counts
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(&a.0)))
+        .map_or(0, |(i, _)| i)
+}
+
+pub fn render(
+    command: &str,
+    context: &CommandContext,
+    metadata_store: &mut MetadataStore,
+) -> String {
+    let (chat_id, user_id) = (context.chat_id, context.user_id);
+    let arguments = super::arguments(command);
+    let time_range = match super::time_range::parse(arguments, context.utc_offset) {
+        Some(time_range) => time_range,
+        None if arguments.is_empty() => TimeRange::all(),
+        None => {
+            return format!(
+                "Tuntematon aika {}.\n\n{}",
+                arguments,
+                super::time_range::USAGE
+            )
+        }
+    };
+
+    let name = metadata_store.get_user_name_or_id(chat_id, user_id);
+    let all_timestamps = metadata_store.get_user_timestamps(chat_id, user_id);
+    let mut timestamps: Vec<i64> = all_timestamps
+        .iter()
+        .copied()
+        .filter(|t| time_range.contains(*t))
+        .collect();
+    if timestamps.is_empty() {
+        return format!("Ei viestejä käyttäjältä {} ({}).", name, time_range.label);
+    }
+    timestamps.sort_unstable();
+
+    let user_message_counts =
+        metadata_store.get_message_counts_by_user(chat_id, time_range.range.clone());
+    let total: usize = user_message_counts.iter().map(|e| e.1).sum();
+    let rank = user_message_counts
+        .iter()
+        .position(|(u, _)| *u == user_id)
+        .unwrap_or(0);
+
+    let mut response = vec![format!(
+        "Tilastot käyttäjälle {} ({}):\n\n",
+        name, time_range.label
+    )];
+    response.push(format!(
+        "Viestejä: {} ({:.1}%), sijoitus {}/{}\n",
+        timestamps.len(),
+        (timestamps.len() * 100) as f64 / total as f64,
+        rank + 1,
+        user_message_counts.len()
+    ));
+
+    if let Some(first) = all_timestamps
+        .iter()
+        .min()
+        .and_then(|t| Utc.timestamp_opt(t + context.utc_offset as i64, 0).single())
+    {
+        response.push(format!(
+            "Ensimmäinen viesti: {}\n",
+            first.format("%Y-%m-%d")
+        ));
+    }
+
+    // Lengths and keyword totals are not stored by time
+    let all_time = if time_range.is_all() {
+        ""
+    } else {
+        " (kaikki ajat)"
+    };
+    if let Some(totals) = metadata_store.get_text_totals(chat_id, user_id) {
+        response.push(format!(
+            "Viestin pituus keskimäärin{}: {:.1} merkkiä, {:.1} sanaa\n",
+            all_time,
+            totals.average_characters(),
+            totals.average_words()
+        ));
+    }
+
+    let mut streak = Streak::default();
+    for timestamp in &timestamps {
+        streak.add(time_range::local_day(*timestamp, context.utc_offset));
+    }
+    response.push(format!("Pisin putki: {} päivää\n", streak.longest));
+
+    let mut hours = [0; 24];
+    let mut weekdays = [0; 7];
+    for timestamp in &timestamps {
+        hours[time_range::local_hour(*timestamp, context.utc_offset)] += 1;
+        weekdays[time_range::local_weekday(*timestamp, context.utc_offset)] += 1;
+    }
+    let hour = busiest(&hours);
+    response.push(format!(
+        "Aktiivisin tunti: {}-{} ({})\n",
+        hour,
+        (hour + 1) % 24,
+        time_range::format_utc_offset(context.utc_offset)
+    ));
+    response.push(format!(
+        "Aktiivisin viikonpäivä: {}\n",
+        time_range::WEEKDAYS[busiest(&weekdays)]
+    ));
+
+    let keyword_scores = metadata_store.get_user_scores_by_keyword(chat_id, user_id);
+    if !keyword_scores.is_empty() {
+        response.push(format!("\nPisteet{}:\n", all_time));
+        for (keyword, score) in keyword_scores {
+            response.push(format!("{}: {}\n", keyword, score));
+        }
+    }
+
+    response.concat()
+}
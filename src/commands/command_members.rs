@@ -0,0 +1,110 @@
+use super::{
+    time_range::{self, TimeRange},
+    CommandContext,
+};
+use crate::{MetadataStore, TelegramUserId};
+use chrono::{TimeZone, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+const TENURE_LIST_LENGTH: usize = 15;
+const LURKER_LIST_LENGTH: usize = 30;
+
+fn format_month(timestamp: i64, utc_offset: i32) -> String {
+    Utc.timestamp_opt(timestamp + utc_offset as i64, 0)
+        .single()
+        .map_or_else(|| timestamp.to_string(), |d| d.format("%Y-%m").to_string())
+}
+
+pub fn render(
+    command: &str,
+    context: &CommandContext,
+    metadata_store: &mut MetadataStore,
+) -> String {
+    let arguments = super::arguments(command);
+    let time_range = match time_range::parse(arguments, context.utc_offset) {
+        Some(time_range) => time_range,
+        None if arguments.is_empty() => TimeRange::all(),
+        None => return format!("Tuntematon aika {}.\n\n{}", arguments, time_range::USAGE),
+    };
+
+    let events = metadata_store.get_membership_events(context.chat_id);
+    if events.is_empty() {
+        return "Ei liittymisiä eikä poistumisia.".into();
+    }
+    let user_name =
+        |user: TelegramUserId| metadata_store.get_user_name_or_id(context.chat_id, user);
+
+    // Joins and leaves by month within the range
+    let mut months: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for event in events.iter().filter(|e| time_range.contains(e.timestamp)) {
+        let month = months
+            .entry(format_month(event.timestamp, context.utc_offset))
+            .or_default();
+        if event.joined {
+            month.0 += 1;
+        } else {
+            month.1 += 1;
+        }
+    }
+    let (joins, leaves) = months.values().fold((0, 0), |total, month| {
+        (total.0 + month.0, total.1 + month.1)
+    });
+
+    let mut response = vec![format!(
+        "Jäsenet ({}): liittyneitä {}, poistuneita {}\n\n",
+        time_range.label, joins, leaves
+    )];
+    for (month, (joins, leaves)) in &months {
+        response.push(format!("{}: +{} -{}\n", month, joins, leaves));
+    }
+
+    // Members whose latest event is a join, with the time of joining
+    let mut joined_at: HashMap<TelegramUserId, Option<i64>> = HashMap::new();
+    for event in events {
+        joined_at.insert(event.user_id, event.joined.then_some(event.timestamp));
+    }
+    let mut members: Vec<(TelegramUserId, i64)> = joined_at
+        .into_iter()
+        .filter_map(|(user, joined)| Some((user, joined?)))
+        .collect();
+    members.sort_unstable_by_key(|(user, joined)| (*joined, *user));
+
+    if !members.is_empty() {
+        let now = time_range::now();
+        response.push("\nJäsenyyden kesto:\n".into());
+        for (user, joined) in members.iter().take(TENURE_LIST_LENGTH) {
+            response.push(format!(
+                "{}: {} päivää\n",
+                user_name(*user),
+                (now - joined) / time_range::SECONDS_PER_DAY
+            ));
+        }
+    }
+
+    let lurkers: Vec<TelegramUserId> = members
+        .iter()
+        .map(|(user, _)| *user)
+        .filter(|user| {
+            metadata_store
+                .get_user_timestamps(context.chat_id, *user)
+                .is_empty()
+        })
+        .collect();
+    if !lurkers.is_empty() {
+        let mut names: Vec<String> = lurkers
+            .iter()
+            .take(LURKER_LIST_LENGTH)
+            .map(|user| user_name(*user))
+            .collect();
+        if lurkers.len() > LURKER_LIST_LENGTH {
+            names.push(format!("+{} muuta", lurkers.len() - LURKER_LIST_LENGTH));
+        }
+        response.push(format!(
+            "\nHiljaiset jäsenet ({}): {}\n",
+            lurkers.len(),
+            names.join(", ")
+        ));
+    }
+
+    response.concat()
+}
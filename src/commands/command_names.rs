@@ -0,0 +1,43 @@
+use super::CommandContext;
+use crate::MetadataStore;
+use chrono::{TimeZone, Utc};
+
+pub fn render(
+    command: &str,
+    context: &CommandContext,
+    metadata_store: &mut MetadataStore,
+) -> String {
+    let user_id = match super::arguments(command) {
+        "" => context.user_id,
+        // Names from other chats are not shown
+        username => match metadata_store.find_user_by_username(username) {
+            Some(user_id) if metadata_store.is_chat_user(context.chat_id, user_id) => user_id,
+            _ => return format!("Käyttäjää {} ei tunneta.", username),
+        },
+    };
+    let name = metadata_store.get_user_name_or_id(context.chat_id, user_id);
+
+    let history = metadata_store.get_user_name_history(user_id);
+    if history.is_empty() {
+        return format!("Ei nimihistoriaa käyttäjälle {}.", name);
+    }
+
+    let mut response = vec![format!("Nimihistoria {}:\n\n", name)];
+    for (timestamp, name) in history {
+        // Names seen before the history was kept have no date
+        let date = timestamp.map_or_else(
+            || "–".to_string(),
+            |timestamp| {
+                Utc.timestamp_opt(timestamp + context.utc_offset as i64, 0)
+                    .single()
+                    .map_or_else(
+                        || timestamp.to_string(),
+                        |d| d.format("%Y-%m-%d").to_string(),
+                    )
+            },
+        );
+        response.push(format!("{}: {}\n", date, name));
+    }
+
+    response.concat()
+}
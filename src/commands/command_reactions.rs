@@ -0,0 +1,26 @@
+use super::CommandContext;
+use crate::MetadataStore;
+
+pub fn render(
+    _command: &str,
+    context: &CommandContext,
+    metadata_store: &mut MetadataStore,
+) -> String {
+    let user_reactions = metadata_store.get_reactions_by_user(context.chat_id);
+    if user_reactions.is_empty() {
+        return "Ei reaktioita.".into();
+    }
+
+    let mut response = vec![String::from("Eniten reaktioita saaneet:\n\n")];
+    for (rank, (user, reactions)) in user_reactions.into_iter().enumerate() {
+        response.push(format!(
+            "{}. {}: {} (antanut {})\n",
+            rank + 1,
+            metadata_store.get_user_name_or_id(context.chat_id, user),
+            reactions.received,
+            reactions.given
+        ));
+    }
+
+    response.concat()
+}
@@ -0,0 +1,87 @@
+use super::{time_range, CommandContext};
+use crate::MetadataStore;
+use chrono::{TimeZone, Utc};
+use std::time::Duration;
+
+const STREAK_LIST_LENGTH: usize = 10;
+
+fn format_day(day: i64) -> String {
+    Utc.timestamp_opt(day * time_range::SECONDS_PER_DAY, 0)
+        .single()
+        .map_or_else(|| day.to_string(), |d| d.format("%Y-%m-%d").to_string())
+}
+
+fn format_time(timestamp: i64, utc_offset: i32) -> String {
+    Utc.timestamp_opt(timestamp + utc_offset as i64, 0)
+        .single()
+        .map_or_else(
+            || timestamp.to_string(),
+            |d| d.format("%Y-%m-%d %H:%M").to_string(),
+        )
+}
+
+pub fn render(
+    _command: &str,
+    context: &CommandContext,
+    metadata_store: &mut MetadataStore,
+) -> String {
+    let records = match metadata_store.get_chat_records(context.chat_id) {
+        Some(records) => records,
+        None => return "Ei ennätyksiä.".into(),
+    };
+    let user_name =
+        |user: crate::TelegramUserId| metadata_store.get_user_name_or_id(context.chat_id, user);
+
+    let mut response = vec![format!(
+        "Ennätykset ({}):\n\n",
+        time_range::format_utc_offset(context.utc_offset)
+    )];
+
+    if let Some((day, count)) = records.busiest_day() {
+        response.push(format!(
+            "Vilkkain päivä: {} ({} viestiä)\n",
+            format_day(day),
+            count
+        ));
+    }
+
+    if let Some((start, end)) = records.longest_silence {
+        let seconds = (end - start).max(0) as u64;
+        response.push(format!(
+            "Pisin hiljaisuus: {} ({} – {})\n",
+            humantime::format_duration(Duration::from_secs(seconds - seconds % 60)),
+            format_time(start, context.utc_offset),
+            format_time(end, context.utc_offset)
+        ));
+    }
+
+    let mut streaks: Vec<_> = records.streaks_by_user.iter().collect();
+    streaks.sort_unstable_by(|a, b| b.1.longest.cmp(&a.1.longest).then(a.0.cmp(b.0)));
+    response.push("\nPisimmät putket:\n".into());
+    for (i, (user, streak)) in streaks.iter().take(STREAK_LIST_LENGTH).enumerate() {
+        let start_day = streak.longest_end_day - streak.longest as i64 + 1;
+        response.push(format!(
+            "{}. {}: {} päivää ({} – {})\n",
+            i + 1,
+            user_name(**user),
+            streak.longest,
+            format_day(start_day),
+            format_day(streak.longest_end_day)
+        ));
+    }
+
+    let today = time_range::local_day(time_range::now(), context.utc_offset);
+    let mut ongoing: Vec<_> = streaks
+        .into_iter()
+        .filter(|(_, s)| s.is_ongoing(today) && s.current > 1)
+        .collect();
+    if !ongoing.is_empty() {
+        ongoing.sort_unstable_by(|a, b| b.1.current.cmp(&a.1.current).then(a.0.cmp(b.0)));
+        response.push("\nKäynnissä olevat putket:\n".into());
+        for (user, streak) in ongoing.into_iter().take(STREAK_LIST_LENGTH) {
+            response.push(format!("{}: {} päivää\n", user_name(*user), streak.current));
+        }
+    }
+
+    response.concat()
+}
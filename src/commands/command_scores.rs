@@ -1,29 +1,153 @@
+use super::CommandContext;
 use crate::{MetadataStore, TelegramChatId};
+use chrono::{Datelike, TimeZone, Utc};
+
+const TIMELINE_BAR_WIDTH: usize = 20;
+
+/// Renders a bar of points per calendar month in the given UTC offset from the first hit to
+/// the last.
+fn render_timeline(hits: &[(i64, i64)], utc_offset: i32) -> String {
+    let months: Vec<(i32, i64)> = hits
+        .iter()
+        .filter_map(|(t, points)| {
+            let date = Utc.timestamp_opt(t + utc_offset as i64, 0).single()?;
+            Some((date, *points))
+        })
+        .map(|(d, points)| (d.year() * 12 + d.month0() as i32, points))
+        .collect();
+    let (first, last) = match (
+        months.iter().map(|m| m.0).min(),
+        months.iter().map(|m| m.0).max(),
+    ) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return String::new(),
+    };
+
+    let mut scores = vec![0i64; (last - first + 1) as usize];
+    for (month, points) in months {
+        scores[(month - first) as usize] += points;
+    }
+    let max = scores
+        .iter()
+        .map(|s| s.unsigned_abs())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let mut response = Vec::new();
+    for (i, score) in scores.into_iter().enumerate() {
+        let month = first + i as i32;
+        let width = (score.unsigned_abs() * TIMELINE_BAR_WIDTH as u64).div_ceil(max);
+        response.push(format!(
+            "{}-{:02} {} {}\n",
+            month / 12,
+            month % 12 + 1,
+            if score < 0 { "░" } else { "█" }.repeat(width as usize),
+            score
+        ));
+    }
+    response.concat()
+}
+
+fn render_user(username: &str, chat_id: TelegramChatId, metadata_store: &MetadataStore) -> String {
+    let user = match metadata_store.find_user_by_username(username) {
+        Some(user) => user,
+        None => return format!("Käyttäjää {} ei tunneta.", username),
+    };
+    let name = metadata_store.get_user_name_or_id(chat_id, user);
+
+    let keyword_scores = metadata_store.get_user_scores_by_keyword(chat_id, user);
+    if keyword_scores.is_empty() {
+        return format!("Ei pisteitä käyttäjälle {}.", name);
+    }
+
+    let mut response = vec![format!("Pisteet käyttäjälle {}:\n\n", name)];
+    for (keyword, score) in keyword_scores {
+        response.push(format!("{}: {}\n", keyword, score));
+    }
+    response.concat()
+}
 
 pub fn render(
     command: &str,
-    chat_id: TelegramChatId,
+    context: &CommandContext,
     metadata_store: &mut MetadataStore,
 ) -> String {
+    let chat_id = context.chat_id;
     let mut response = Vec::new();
-    if let Some((_, word)) = command.split_once(|c: char| c.is_whitespace()) {
-        let user_scores = metadata_store.get_scores_by_user(word, chat_id);
+    if let Some((_, arguments)) = command.split_once(|c: char| c.is_whitespace()) {
+        let arguments = arguments.trim();
+        let (word, time_str) = arguments
+            .split_once(|c: char| c.is_whitespace())
+            .map_or((arguments, ""), |(w, t)| (w, t.trim()));
+        let (timeline, time_str) = match time_str.strip_prefix("aikajana") {
+            Some(time_str) => (true, time_str.trim()),
+            None => (false, time_str),
+        };
+        if word.starts_with('@') {
+            return render_user(word, chat_id, metadata_store);
+        }
+
+        let time_range = super::time_range::parse(time_str, context.utc_offset);
+        let from = time_range
+            .as_ref()
+            .map_or("kaikki", |time_range| time_range.label.as_str());
+
+        if timeline {
+            let hits: Vec<(i64, i64)> = metadata_store
+                .get_keyword_hits(word, chat_id)
+                .into_iter()
+                .filter(|(t, _)| time_range.as_ref().is_none_or(|r| r.contains(*t)))
+                .collect();
+            if hits.is_empty() {
+                response.push(format!("Ei pisteitä sanalle {} ({}).", word, from));
+            } else {
+                response.push(format!("Aikajana sanalle {} ({}):\n\n", word, from));
+                response.push(render_timeline(&hits, context.utc_offset));
+            }
+            return response.concat();
+        }
+
+        let user_scores = metadata_store.get_scores_by_user(
+            word,
+            chat_id,
+            time_range.as_ref().map(|r| r.range.clone()),
+        );
         if user_scores.is_empty() {
-            response.push(format!("Ei pisteitä sanalle {}.", word));
+            response.push(format!("Ei pisteitä sanalle {} ({}).", word, from));
         } else {
-            response.push(format!("Pisteet sanalle {}:\n\n", word));
+            response.push(format!("Pisteet sanalle {} ({}):\n\n", word, from));
             for (user, score) in user_scores {
                 response.push(format!(
                     "{}: {}\n",
-                    metadata_store
-                        .get_user_name(user)
-                        .unwrap_or(&user.to_string()),
+                    metadata_store.get_user_name_or_id(chat_id, user),
                     score
                 ));
             }
         }
     } else {
-        response.push("Käyttöohje: /pisteet sana".into());
+        let keyword_totals = metadata_store.get_keyword_totals(chat_id);
+        if keyword_totals.is_empty() {
+            response.push("Ei pisteitä.\n\n".into());
+        } else {
+            response.push("Pisteet sanoittain:\n\n".into());
+            for keyword_total in keyword_totals {
+                response.push(format!(
+                    "{}: {}",
+                    keyword_total.keyword, keyword_total.total
+                ));
+                if let Some((user, score)) = keyword_total.leader {
+                    response.push(format!(
+                        " (johdossa {} {})",
+                        metadata_store.get_user_name_or_id(chat_id, user),
+                        score
+                    ));
+                }
+                response.push("\n".into());
+            }
+            response.push("\n".into());
+        }
+        response.push("Käyttöohje: /pisteet sana|@käyttäjä [aikajana] [aika, esim. 30d]".into());
     }
 
     response.concat()
@@ -0,0 +1,107 @@
+use super::{
+    time_range::{self, TimeRange},
+    CommandContext,
+};
+use crate::{MetadataStore, TelegramUserId};
+use std::{collections::HashMap, time::Duration};
+
+const DEFAULT_GAP: Duration = Duration::from_secs(60 * 60);
+
+const USAGE: &str = "Käyttöohje: /keskustelut [aika] [tauko 1h]";
+
+pub fn render(
+    command: &str,
+    context: &CommandContext,
+    metadata_store: &mut MetadataStore,
+) -> String {
+    let arguments = super::arguments(command);
+    let (arguments, gap) = match arguments.split_once("tauko") {
+        Some((arguments, gap)) => match humantime::parse_duration(gap.trim()) {
+            Ok(gap) => (arguments.trim(), gap),
+            Err(_) => return format!("Tuntematon tauko {}.\n\n{}", gap.trim(), USAGE),
+        },
+        None => (arguments, DEFAULT_GAP),
+    };
+    let time_range = match time_range::parse(arguments, context.utc_offset) {
+        Some(time_range) => time_range,
+        None if arguments.is_empty() => TimeRange::all(),
+        None => {
+            return format!(
+                "Tuntematon aika {}.\n\n{}\n{}",
+                arguments,
+                USAGE,
+                time_range::USAGE
+            )
+        }
+    };
+
+    let sessions = metadata_store.get_sessions(
+        context.chat_id,
+        time_range.range.clone(),
+        gap.as_secs().try_into().unwrap_or(i64::MAX),
+    );
+    if sessions.is_empty() {
+        return format!("Ei keskusteluja ({}).", time_range.label);
+    }
+
+    let mut starts_by_user: HashMap<TelegramUserId, usize> = HashMap::new();
+    let mut sessions_by_user: HashMap<TelegramUserId, usize> = HashMap::new();
+    for session in &sessions {
+        if session.breaks_silence {
+            *starts_by_user.entry(session.starter).or_insert(0) += 1;
+        }
+        for user in &session.participants {
+            *sessions_by_user.entry(*user).or_insert(0) += 1;
+        }
+    }
+    let count = sessions.len();
+    let average_length = sessions.iter().map(|s| s.end - s.start).sum::<i64>() / count as i64;
+    let average_messages = sessions.iter().map(|s| s.message_count).sum::<usize>() / count;
+
+    let user_name =
+        |user: TelegramUserId| metadata_store.get_user_name_or_id(context.chat_id, user);
+    let sorted = |counts: HashMap<TelegramUserId, usize>| {
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    };
+
+    let mut response = vec![format!(
+        "Keskustelut ({}, tauko {}):\n\n",
+        time_range.label,
+        humantime::format_duration(gap)
+    )];
+    response.push(format!(
+        "Keskusteluja: {}, keskimääräinen kesto {}, keskimäärin {} viestiä\n",
+        count,
+        humantime::format_duration(Duration::from_secs(
+            (average_length - average_length % 60) as u64
+        )),
+        average_messages
+    ));
+
+    let silence_breaks: usize = starts_by_user.values().sum();
+    if silence_breaks > 0 {
+        response.push("\nHiljaisuuden rikkojat:\n".into());
+        for (user, starts) in sorted(starts_by_user) {
+            response.push(format!(
+                "{}: {} ({:.1}%)\n",
+                user_name(user),
+                starts,
+                (starts * 100) as f64 / silence_breaks as f64
+            ));
+        }
+    }
+
+    response.push("\nOsallistuminen:\n".into());
+    for (user, participated) in sorted(sessions_by_user) {
+        response.push(format!(
+            "{}: {} ({:.1}%)\n",
+            user_name(user),
+            participated,
+            (participated * 100) as f64 / count as f64
+        ));
+    }
+
+    response.concat()
+}
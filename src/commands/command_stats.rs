@@ -1,23 +1,246 @@
-use crate::{MetadataStore, TelegramChatId};
+use super::time_range::TimeRange;
+use super::CommandContext;
+use crate::{
+    message_kinds::MessageKind, metadata_store::TextTotals, MetadataStore, TelegramChatId,
+    TelegramThreadId, TelegramUserId,
+};
+use std::ops::Range;
+
+/// A whole chat or one of its forum topics.
+struct Scope {
+    chat_id: TelegramChatId,
+    thread_id: Option<TelegramThreadId>,
+}
+
+impl Scope {
+    fn message_counts(
+        &self,
+        metadata_store: &MetadataStore,
+        range: Range<i64>,
+    ) -> Vec<(TelegramUserId, usize)> {
+        match self.thread_id {
+            Some(thread_id) => {
+                metadata_store.get_topic_message_counts_by_user(self.chat_id, thread_id, range)
+            }
+            None => metadata_store.get_message_counts_by_user(self.chat_id, range),
+        }
+    }
+
+    fn heading(&self, metadata_store: &MetadataStore) -> String {
+        match self.thread_id {
+            Some(thread_id) => format!(
+                "Aihe {}\n",
+                metadata_store
+                    .get_topic_name(self.chat_id, thread_id)
+                    .map_or_else(|| thread_id.to_string(), String::from)
+            ),
+            None => String::new(),
+        }
+    }
+}
+
+fn render_comparison(
+    scope: &Scope,
+    metadata_store: &MetadataStore,
+    time_range: &TimeRange,
+    previous: &TimeRange,
+) -> String {
+    let user_message_counts = scope.message_counts(metadata_store, time_range.range.clone());
+    let previous_counts = scope.message_counts(metadata_store, previous.range.clone());
+    let total: usize = user_message_counts.iter().map(|e| e.1).sum();
+    let previous_total: usize = previous_counts.iter().map(|e| e.1).sum();
+
+    let mut response = vec![format!(
+        "{}Viestejä yhteensä {}: {} ({}: {}, {:+})\n\n",
+        scope.heading(metadata_store),
+        time_range.label,
+        total,
+        previous.label,
+        previous_total,
+        total as i64 - previous_total as i64
+    )];
+
+    for (rank, (user, count)) in user_message_counts.into_iter().enumerate() {
+        let previous = previous_counts.iter().position(|(u, _)| *u == user);
+        let previous_count = previous.map_or(0, |i| previous_counts[i].1);
+        let rank_change = match previous {
+            Some(previous_rank) if previous_rank > rank => format!(" ↑{}", previous_rank - rank),
+            Some(previous_rank) if previous_rank < rank => format!(" ↓{}", rank - previous_rank),
+            Some(_) => String::new(),
+            None => " (uusi)".into(),
+        };
+        response.push(format!(
+            "{}. {}: {} ({:+}){}\n",
+            rank + 1,
+            metadata_store.get_user_name_or_id(scope.chat_id, user),
+            count,
+            count as i64 - previous_count as i64,
+            rank_change
+        ));
+    }
+
+    response.concat()
+}
+
+const KIND_LIST_LENGTH: usize = 20;
+
+fn render_kinds(scope: &Scope, metadata_store: &MetadataStore) -> String {
+    let mut user_counts = metadata_store.get_message_kind_counts(scope.chat_id, scope.thread_id);
+    if user_counts.is_empty() {
+        return "Viestityyppejä ei ole vielä tilastoitu.".into();
+    }
+    user_counts.sort_unstable_by_key(|(user, counts)| {
+        (std::cmp::Reverse(counts.values().sum::<u64>()), *user)
+    });
+
+    let mut response = vec![format!(
+        "{}Viestityypit:\n\n",
+        scope.heading(metadata_store)
+    )];
+    for kind in MessageKind::ALL {
+        let total: u64 = user_counts
+            .iter()
+            .filter_map(|(_, counts)| counts.get(&kind))
+            .sum();
+        if total > 0 {
+            response.push(format!("{}: {}\n", kind.name(), total));
+        }
+    }
+    response.push("\n".into());
+
+    let others = user_counts.len().saturating_sub(KIND_LIST_LENGTH);
+    for (user, counts) in user_counts.into_iter().take(KIND_LIST_LENGTH) {
+        let kinds: Vec<String> = MessageKind::ALL
+            .iter()
+            .filter_map(|kind| counts.get(kind).map(|n| format!("{} {}", kind.name(), n)))
+            .collect();
+        response.push(format!(
+            "{}: {}\n",
+            metadata_store.get_user_name_or_id(scope.chat_id, user),
+            kinds.join(", ")
+        ));
+    }
+    if others > 0 {
+        response.push(format!("+{} muuta\n", others));
+    }
+
+    response.concat()
+}
+
+/// Averages of fewer messages are too random to rank
+const MIN_MESSAGES_FOR_LENGTH_RANKING: u64 = 10;
+
+fn render_lengths(scope: &Scope, metadata_store: &MetadataStore) -> String {
+    let user_totals = metadata_store.get_text_totals_by_user(scope.chat_id, scope.thread_id);
+    if user_totals.is_empty() {
+        return "Viestien pituuksia ei ole vielä tilastoitu.".into();
+    }
+    let user_name = |user: TelegramUserId| metadata_store.get_user_name_or_id(scope.chat_id, user);
+
+    let total = user_totals
+        .iter()
+        .fold(TextTotals::default(), |total, (_, totals)| TextTotals {
+            messages: total.messages + totals.messages,
+            characters: total.characters + totals.characters,
+            words: total.words + totals.words,
+        });
+    let mut response = vec![format!(
+        "{}Viestien pituus keskimäärin {:.1} merkkiä, {:.1} sanaa\n",
+        scope.heading(metadata_store),
+        total.average_characters(),
+        total.average_words()
+    )];
+
+    let ranked: Vec<_> = user_totals
+        .iter()
+        .filter(|(_, totals)| totals.messages >= MIN_MESSAGES_FOR_LENGTH_RANKING)
+        .collect();
+    let Some((most_verbose, _)) = ranked.first() else {
+        response.push(format!(
+            "Puheliaimman valintaan tarvitaan vähintään {} viestiä.\n",
+            MIN_MESSAGES_FOR_LENGTH_RANKING
+        ));
+        return response.concat();
+    };
+    response.push(format!(
+        "Puheliain: {}\n\nVähintään {} viestiä lähettäneet:\n",
+        user_name(*most_verbose),
+        MIN_MESSAGES_FOR_LENGTH_RANKING
+    ));
+
+    for (rank, (user, totals)) in ranked.into_iter().enumerate() {
+        response.push(format!(
+            "{}. {}: {:.1} merkkiä, {:.1} sanaa ({} viestiä)\n",
+            rank + 1,
+            user_name(*user),
+            totals.average_characters(),
+            totals.average_words(),
+            totals.messages
+        ));
+    }
+
+    response.concat()
+}
 
 pub fn render(
     command: &str,
-    chat_id: TelegramChatId,
+    context: &CommandContext,
     metadata_store: &mut MetadataStore,
 ) -> String {
-    let (after_unix, from) = super::convert_time(command).unwrap_or((0, "kaikki"));
+    let chat_id = context.chat_id;
+    let arguments = super::arguments(command);
+    // In forum topics, stats are of the topic unless the whole chat is asked for
+    let (arguments, thread_id) = match arguments.strip_prefix("koko") {
+        Some(arguments) if arguments.is_empty() || arguments.starts_with(char::is_whitespace) => {
+            (arguments.trim(), None)
+        }
+        _ => (arguments, context.thread_id),
+    };
+    let scope = Scope { chat_id, thread_id };
+    match arguments {
+        "tyypit" => return render_kinds(&scope, metadata_store),
+        "pituus" => return render_lengths(&scope, metadata_store),
+        _ => {}
+    }
+    let (arguments, compare) = match arguments.strip_suffix("vs") {
+        Some(arguments) if arguments.is_empty() || arguments.ends_with(char::is_whitespace) => {
+            (arguments.trim(), true)
+        }
+        _ => (arguments, false),
+    };
+    let time_range = match super::time_range::parse(arguments, context.utc_offset) {
+        Some(time_range) => time_range,
+        None if arguments.is_empty() => TimeRange::all(),
+        None => {
+            return format!(
+                "Tuntematon aika {}.\n\n{}",
+                arguments,
+                super::time_range::USAGE
+            )
+        }
+    };
+
+    if compare {
+        return match time_range.previous() {
+            Some(previous) => render_comparison(&scope, metadata_store, &time_range, &previous),
+            None => "Vertailuun tarvitaan aika, esim. /tilasto 7d vs".into(),
+        };
+    }
 
-    let user_message_counts = metadata_store.get_message_counts_by_user(chat_id, after_unix);
+    let user_message_counts = scope.message_counts(metadata_store, time_range.range.clone());
     let total: usize = user_message_counts.iter().map(|e| e.1).sum();
 
-    let mut response = vec![format!("Viestejä yhteensä {}: {}\n\n", from, total)];
+    let mut response = vec![format!(
+        "{}Viestejä yhteensä {}: {}\n\n",
+        scope.heading(metadata_store),
+        time_range.label,
+        total
+    )];
 
     for (user, count) in user_message_counts {
         response.push(format!(
             "{}: {} ({:.1}%)\n",
-            metadata_store
-                .get_user_name(user)
-                .unwrap_or(&user.to_string()),
+            metadata_store.get_user_name_or_id(scope.chat_id, user),
             count,
             (count * 100) as f64 / total as f64
         ));
@@ -0,0 +1,410 @@
+use chrono::{Datelike, NaiveDate, TimeZone, Utc};
+use std::{
+    ops::Range,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const MONTHS: [&str; 12] = [
+    "tammikuu",
+    "helmikuu",
+    "maaliskuu",
+    "huhtikuu",
+    "toukokuu",
+    "kesäkuu",
+    "heinäkuu",
+    "elokuu",
+    "syyskuu",
+    "lokakuu",
+    "marraskuu",
+    "joulukuu",
+];
+
+pub const WEEKDAYS: [&str; 7] = [
+    "maanantai",
+    "tiistai",
+    "keskiviikko",
+    "torstai",
+    "perjantai",
+    "lauantai",
+    "sunnuntai",
+];
+
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+pub const USAGE: &str = "Aika: 7d, tänään, eilen, tämä/viime viikko, tämä/viime kuu, \
+tämä/viime vuosi, 2024, 2024-01, 2024-01-31, tammikuu [2024] tai väli alku..loppu";
+
+/// A half-open range of unix time with a human readable label.
+#[derive(Debug, Clone)]
+pub struct TimeRange {
+    pub range: Range<i64>,
+    pub label: String,
+}
+
+impl TimeRange {
+    pub fn all() -> Self {
+        Self {
+            range: i64::MIN..i64::MAX,
+            label: "kaikki".into(),
+        }
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.range == (i64::MIN..i64::MAX)
+    }
+
+    pub fn contains(&self, timestamp: i64) -> bool {
+        self.range.contains(&timestamp)
+    }
+
+    /// Returns the range of equal length right before this one. Ranges ending in the future
+    /// are considered to end now. `None` for ranges without a start.
+    pub fn previous(&self) -> Option<Self> {
+        if self.range.start == i64::MIN {
+            return None;
+        }
+        let end = if self.range.end == i64::MAX {
+            now()
+        } else {
+            self.range.end
+        };
+        let length = end.checked_sub(self.range.start)?;
+        Some(Self {
+            range: self.range.start.checked_sub(length)?..self.range.start,
+            label: format!("edellinen {}", self.label),
+        })
+    }
+}
+
+/// Parses a UTC offset such as `2`, `+02:00` or `-5:30` to seconds.
+pub fn parse_utc_offset(s: &str) -> Option<i32> {
+    let s = s.trim();
+    let (sign, s) = match s.strip_prefix('-') {
+        Some(s) => (-1, s),
+        None => (1, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (hours, minutes) = s.split_once(':').unwrap_or((s, "0"));
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    if !is_number(hours) || !is_number(minutes) {
+        return None;
+    }
+    let (hours, minutes): (i32, i32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Formats a UTC offset in seconds as `UTC`, `UTC+2` or `UTC-5:30`.
+pub fn format_utc_offset(utc_offset: i32) -> String {
+    let (hours, minutes) = (utc_offset / 3600, (utc_offset.abs() % 3600) / 60);
+    match (hours, minutes) {
+        (0, 0) => "UTC".into(),
+        (_, 0) => format!("UTC{:+}", hours),
+        _ => format!(
+            "UTC{}{}:{:02}",
+            if utc_offset < 0 { '-' } else { '+' },
+            hours.abs(),
+            minutes
+        ),
+    }
+}
+
+/// Returns the day number since 1970-01-01 of a timestamp in the given UTC offset.
+pub fn local_day(timestamp: i64, utc_offset: i32) -> i64 {
+    (timestamp + utc_offset as i64).div_euclid(SECONDS_PER_DAY)
+}
+
+/// Returns the hour of day (0-23) of a timestamp in the given UTC offset.
+pub fn local_hour(timestamp: i64, utc_offset: i32) -> usize {
+    ((timestamp + utc_offset as i64).rem_euclid(SECONDS_PER_DAY) / 3600) as usize
+}
+
+/// Returns the weekday (0 for monday) of a timestamp in the given UTC offset.
+pub fn local_weekday(timestamp: i64, utc_offset: i32) -> usize {
+    // 1970-01-01 was a thursday
+    (local_day(timestamp, utc_offset) + 3).rem_euclid(7) as usize
+}
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs().try_into().unwrap_or(i64::MAX))
+}
+
+fn day_start(date: NaiveDate, utc_offset: i32) -> i64 {
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .timestamp()
+        - utc_offset as i64
+}
+
+fn add_days(date: NaiveDate, days: i32) -> Option<NaiveDate> {
+    NaiveDate::from_num_days_from_ce_opt(date.num_days_from_ce() + days)
+}
+
+fn month_range(year: i32, month: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let end = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    };
+    Some((start, end))
+}
+
+fn year_range(year: i32) -> Option<(NaiveDate, NaiveDate)> {
+    Some((
+        NaiveDate::from_ymd_opt(year, 1, 1)?,
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?,
+    ))
+}
+
+/// Parses a calendar period to a half-open range of dates.
+fn parse_period(s: &str, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let monday = add_days(today, -(today.weekday().num_days_from_monday() as i32))?;
+    let last_month = if today.month() == 1 {
+        (today.year() - 1, 12)
+    } else {
+        (today.year(), today.month() - 1)
+    };
+
+    match s {
+        "tänään" => return Some((today, add_days(today, 1)?)),
+        "eilen" => return Some((add_days(today, -1)?, today)),
+        "tämä viikko" => return Some((monday, add_days(monday, 7)?)),
+        "viime viikko" => return Some((add_days(monday, -7)?, monday)),
+        "tämä kuu" => return month_range(today.year(), today.month()),
+        "viime kuu" => return month_range(last_month.0, last_month.1),
+        "tämä vuosi" => return year_range(today.year()),
+        "viime vuosi" => return year_range(today.year() - 1),
+        _ => {}
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Some((date, add_days(date, 1)?));
+    }
+
+    if let Some((year, month)) = s.split_once('-') {
+        return month_range(year.parse().ok()?, month.parse().ok()?);
+    }
+
+    if s.len() == 4 {
+        if let Ok(year) = s.parse() {
+            return year_range(year);
+        }
+    }
+
+    // A month name refers to the latest such month, unless a year is given
+    let mut words = s.split_whitespace();
+    let month_name = words.next()?;
+    let month = MONTHS.iter().position(|m| *m == month_name)? as u32 + 1;
+    let year = match words.next() {
+        Some(year) => year.parse().ok()?,
+        None if month > today.month() => today.year() - 1,
+        None => today.year(),
+    };
+    if words.next().is_some() {
+        return None;
+    }
+    month_range(year, month)
+}
+
+/// Parses a time range argument such as `7d`, `eilen`, `tammikuu` or `2024-01..2024-03`.
+/// Ranges exclude the period after `..`. Calendar days start at midnight in `utc_offset`.
+pub fn parse(s: &str, utc_offset: i32) -> Option<TimeRange> {
+    let today = (Utc::now() + chrono::Duration::seconds(utc_offset.into()))
+        .naive_utc()
+        .date();
+    parse_at(s, utc_offset, today)
+}
+
+fn parse_at(s: &str, utc_offset: i32, today: NaiveDate) -> Option<TimeRange> {
+    let s = s.trim();
+    if s.is_empty() {
+        return None;
+    }
+
+    let range = if let Some((start, end)) = s.split_once("..") {
+        let start = match start.trim() {
+            "" => i64::MIN,
+            start => day_start(parse_period(start, today)?.0, utc_offset),
+        };
+        let end = match end.trim() {
+            "" => i64::MAX,
+            end => day_start(parse_period(end, today)?.0, utc_offset),
+        };
+        // Inverted and empty ranges are most likely typos
+        if start >= end {
+            return None;
+        }
+        start..end
+    } else if let Some((start, end)) = parse_period(s, today) {
+        day_start(start, utc_offset)..day_start(end, utc_offset)
+    } else {
+        let duration = humantime::parse_duration(s).ok()?;
+        let seconds: i64 = duration.as_secs().try_into().ok()?;
+        now().checked_sub(seconds)?..i64::MAX
+    };
+
+    Some(TimeRange {
+        range,
+        label: s.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn period(s: &str) -> Option<(NaiveDate, NaiveDate)> {
+        // A wednesday
+        parse_period(s, date(2024, 3, 13))
+    }
+
+    #[test]
+    fn utc_offsets() {
+        assert_eq!(parse_utc_offset("2"), Some(7200));
+        assert_eq!(parse_utc_offset("+02:00"), Some(7200));
+        assert_eq!(parse_utc_offset("-05:30"), Some(-19800));
+        assert_eq!(parse_utc_offset(" 0 "), Some(0));
+        assert_eq!(parse_utc_offset("15"), None);
+        assert_eq!(parse_utc_offset("2:60"), None);
+        assert_eq!(parse_utc_offset("+-2"), None);
+        assert_eq!(parse_utc_offset("2:-5"), None);
+        assert_eq!(parse_utc_offset(""), None);
+        assert_eq!(parse_utc_offset("abc"), None);
+    }
+
+    #[test]
+    fn format_utc_offsets() {
+        assert_eq!(format_utc_offset(0), "UTC");
+        assert_eq!(format_utc_offset(7200), "UTC+2");
+        assert_eq!(format_utc_offset(-19800), "UTC-5:30");
+    }
+
+    #[test]
+    fn local_time() {
+        // 2024-03-13 23:30 UTC is thursday 01:30 in UTC+2
+        let timestamp = 1710372600;
+        assert_eq!(local_hour(timestamp, 0), 23);
+        assert_eq!(local_hour(timestamp, 7200), 1);
+        assert_eq!(local_weekday(timestamp, 0), 2);
+        assert_eq!(local_weekday(timestamp, 7200), 3);
+        assert_eq!(local_day(-1, 0), -1);
+    }
+
+    #[test]
+    fn relative_periods() {
+        assert_eq!(
+            period("tänään"),
+            Some((date(2024, 3, 13), date(2024, 3, 14)))
+        );
+        assert_eq!(
+            period("eilen"),
+            Some((date(2024, 3, 12), date(2024, 3, 13)))
+        );
+        assert_eq!(
+            period("tämä viikko"),
+            Some((date(2024, 3, 11), date(2024, 3, 18)))
+        );
+        assert_eq!(
+            period("viime viikko"),
+            Some((date(2024, 3, 4), date(2024, 3, 11)))
+        );
+        assert_eq!(
+            period("viime kuu"),
+            Some((date(2024, 2, 1), date(2024, 3, 1)))
+        );
+        assert_eq!(
+            period("viime vuosi"),
+            Some((date(2023, 1, 1), date(2024, 1, 1)))
+        );
+        assert_eq!(
+            parse_period("viime kuu", date(2024, 1, 5)),
+            Some((date(2023, 12, 1), date(2024, 1, 1)))
+        );
+    }
+
+    #[test]
+    fn absolute_periods() {
+        assert_eq!(
+            period("2024-02-29"),
+            Some((date(2024, 2, 29), date(2024, 3, 1)))
+        );
+        assert_eq!(
+            period("2023-12"),
+            Some((date(2023, 12, 1), date(2024, 1, 1)))
+        );
+        assert_eq!(period("2022"), Some((date(2022, 1, 1), date(2023, 1, 1))));
+        // Month names refer to the latest such month
+        assert_eq!(
+            period("helmikuu"),
+            Some((date(2024, 2, 1), date(2024, 3, 1)))
+        );
+        assert_eq!(
+            period("huhtikuu"),
+            Some((date(2023, 4, 1), date(2023, 5, 1)))
+        );
+        assert_eq!(
+            period("huhtikuu 2020"),
+            Some((date(2020, 4, 1), date(2020, 5, 1)))
+        );
+    }
+
+    #[test]
+    fn invalid_periods() {
+        assert_eq!(period("2023-02-29"), None);
+        assert_eq!(period("2024-13"), None);
+        assert_eq!(period("2024-00"), None);
+        assert_eq!(period("huhtikuu x"), None);
+        assert_eq!(period("huhtikuu 2020 x"), None);
+        assert_eq!(period("maanantai"), None);
+    }
+
+    #[test]
+    fn ranges() {
+        let today = date(2024, 3, 13);
+        let range = parse_at("2024-01..2024-03", 0, today).unwrap();
+        assert_eq!(range.range, 1704067200..1709251200);
+        assert_eq!(range.label, "2024-01..2024-03");
+
+        let range = parse_at("2024-01-01", 7200, today).unwrap();
+        assert_eq!(range.range, 1704067200 - 7200..1704153600 - 7200);
+
+        assert_eq!(parse_at("..2024", 0, today).unwrap().range.start, i64::MIN);
+        assert_eq!(parse_at("2024..", 0, today).unwrap().range.end, i64::MAX);
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        let today = date(2024, 3, 13);
+        assert!(parse_at("", 0, today).is_none());
+        assert!(parse_at("2024-03..2024-01", 0, today).is_none());
+        assert!(parse_at("2024-01..2024-01", 0, today).is_none());
+        assert!(parse_at("2024-01..foo", 0, today).is_none());
+        assert!(parse_at("foo", 0, today).is_none());
+    }
+
+    #[test]
+    fn durations() {
+        let range = parse_at("7d", 0, date(2024, 3, 13)).unwrap();
+        assert_eq!(range.range.end, i64::MAX);
+        assert!((now() - range.range.start - 7 * SECONDS_PER_DAY).abs() <= 1);
+    }
+
+    #[test]
+    fn previous_ranges() {
+        let range = parse_at("2024-02", 0, date(2024, 3, 13)).unwrap();
+        let previous = range.previous().unwrap();
+        assert_eq!(previous.range.end, range.range.start);
+        assert_eq!(
+            previous.range.end - previous.range.start,
+            range.range.end - range.range.start
+        );
+        assert!(TimeRange::all().previous().is_none());
+    }
+}
@@ -0,0 +1,661 @@
+use crate::{commands::time_range, TelegramChatId, TelegramUserId};
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, str::FromStr, time::Duration};
+use thiserror::Error;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Keyword definition is empty")]
+    Empty,
+    #[error("Keyword group {0} has no patterns")]
+    NoPatterns(String),
+    #[error("Invalid keyword weight {0}")]
+    Weight(String),
+    #[error("Too many fields in keyword definition {0}")]
+    TooManyFields(String),
+    #[error("Invalid keyword regex")]
+    Regex(#[from] regex::Error),
+    #[error("Unknown acknowledgement mode {0}")]
+    UnknownAcknowledgement(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchMode {
+    /// The pattern must be a whole word, for example `kesko`.
+    Word,
+    /// Words starting with the pattern match, for example `kesko*` matches `keskossa`.
+    Prefix,
+    /// The pattern is a regular expression, for example `/k-?market/`.
+    Regex,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pattern {
+    pub text: String,
+    pub mode: MatchMode,
+}
+
+impl FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let pattern = if let Some(regex) = s
+            .strip_prefix('/')
+            .and_then(|s| s.strip_suffix('/'))
+            .filter(|r| !r.is_empty())
+        {
+            // Fail early on invalid expressions
+            Regex::new(regex)?;
+            Self {
+                text: regex.into(),
+                mode: MatchMode::Regex,
+            }
+        } else if let Some(prefix) = s.strip_suffix('*') {
+            Self {
+                text: prefix.into(),
+                mode: MatchMode::Prefix,
+            }
+        } else {
+            Self {
+                text: s.into(),
+                mode: MatchMode::Word,
+            }
+        };
+
+        if pattern.text.is_empty() {
+            Err(Error::Empty)
+        } else {
+            Ok(pattern)
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            MatchMode::Word => write!(f, "{}", self.text),
+            MatchMode::Prefix => write!(f, "{}*", self.text),
+            MatchMode::Regex => write!(f, "/{}/", self.text),
+        }
+    }
+}
+
+/// A named keyword group. A match of any of the patterns scores under the group name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keyword {
+    pub name: String,
+    pub patterns: Vec<Pattern>,
+    /// Points scored per match, negative for penalty words
+    #[serde(default = "default_weight")]
+    pub weight: i64,
+}
+
+fn default_weight() -> i64 {
+    1
+}
+
+impl FromStr for Keyword {
+    type Err = Error;
+
+    /// Parses either a plain `pattern` or a group `name=pattern1|pattern2`. A group may be
+    /// followed by a weight, as in `kesko=kesko=2` or `kirosana=perkele|saatana=-1`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = split_patterns(s, '=').collect();
+        let (name, patterns, weight) = match fields.as_slice() {
+            [patterns] => (*patterns, *patterns, 1),
+            [name, patterns] => (*name, *patterns, 1),
+            [name, patterns, weight] => (
+                *name,
+                *patterns,
+                weight
+                    .trim()
+                    .parse()
+                    .map_err(|_| Error::Weight(weight.trim().into()))?,
+            ),
+            _ => return Err(Error::TooManyFields(s.trim().into())),
+        };
+
+        let patterns = split_patterns(patterns, '|')
+            .filter(|p| !p.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Pattern>, _>>()?;
+
+        // Plain patterns are named after their text
+        let name = match (name.trim(), patterns.as_slice()) {
+            (_, []) => return Err(Error::NoPatterns(name.trim().into())),
+            (name, [pattern]) if name == pattern.to_string() => pattern.text.clone(),
+            (name, _) => name.into(),
+        };
+        if name.is_empty() {
+            return Err(Error::Empty);
+        }
+
+        Ok(Self {
+            name,
+            patterns,
+            weight,
+        })
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let patterns: Vec<String> = self.patterns.iter().map(Pattern::to_string).collect();
+        if patterns.len() == 1 && self.patterns[0].text == self.name && self.weight == 1 {
+            return write!(f, "{}", patterns[0]);
+        }
+        write!(f, "{}={}", self.name, patterns.join("|"))?;
+        if self.weight != 1 {
+            write!(f, "={}", self.weight)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits `s` at `separator`, except inside `/regex/` patterns.
+fn split_patterns(s: &str, separator: char) -> impl Iterator<Item = &str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_regex = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_regex => escaped = true,
+            '/' if in_regex => in_regex = false,
+            '/' if s[start..i].trim_end().ends_with(['=', '|'])
+                || s[start..i].trim().is_empty() =>
+            {
+                in_regex = true
+            }
+            c if c == separator && !in_regex => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts.into_iter()
+}
+
+/// Parses a comma separated list of keyword definitions, such as `MFJ_KEYWORDS`.
+pub fn parse_list(s: &str) -> Result<Vec<Keyword>, Error> {
+    split_patterns(s, ',')
+        .filter(|d| !d.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Normalizes text for matching: Unicode case folding, and optionally diacritic removal.
+fn fold(text: &str, ignore_diacritics: bool) -> String {
+    if ignore_diacritics {
+        let stripped: String = text.nfd().filter(|c| !is_combining_mark(*c)).collect();
+        caseless::default_case_fold_str(&stripped)
+    } else {
+        caseless::default_case_fold_str(&text.nfc().collect::<String>())
+    }
+}
+
+/// Normalizes a regex source like [`fold`] does, except for case, which is left to case
+/// insensitive matching so that escapes such as `\W` keep their meaning.
+fn fold_regex(source: &str, ignore_diacritics: bool) -> String {
+    if ignore_diacritics {
+        source.nfd().filter(|c| !is_combining_mark(*c)).collect()
+    } else {
+        source.nfc().collect()
+    }
+}
+
+fn is_word_boundary(c: Option<char>) -> bool {
+    c.is_none_or(|c| !c.is_alphanumeric())
+}
+
+pub struct KeywordFinder {
+    keywords: Vec<Keyword>,
+    ignore_diacritics: bool,
+    finder: AhoCorasick,
+    keyword_index_and_mode_by_pattern: Vec<(usize, MatchMode)>,
+    regexes: Vec<(usize, Regex)>,
+}
+
+impl KeywordFinder {
+    pub fn new(keywords: Vec<Keyword>, ignore_diacritics: bool) -> Result<Self, Error> {
+        let mut patterns = Vec::new();
+        let mut keyword_index_and_mode_by_pattern = Vec::new();
+        let mut regexes = Vec::new();
+        for (i, keyword) in keywords.iter().enumerate() {
+            for pattern in &keyword.patterns {
+                match pattern.mode {
+                    MatchMode::Word | MatchMode::Prefix => {
+                        patterns.push(fold(&pattern.text, ignore_diacritics));
+                        keyword_index_and_mode_by_pattern.push((i, pattern.mode));
+                    }
+                    MatchMode::Regex => {
+                        let regex =
+                            RegexBuilder::new(&fold_regex(&pattern.text, ignore_diacritics))
+                                .case_insensitive(true)
+                                .build()?;
+                        regexes.push((i, regex));
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            keywords,
+            ignore_diacritics,
+            finder: AhoCorasickBuilder::new().build(&patterns),
+            keyword_index_and_mode_by_pattern,
+            regexes,
+        })
+    }
+
+    pub fn keywords(&self) -> &[Keyword] {
+        &self.keywords
+    }
+
+    /// Returns the keyword groups of all matches in `text` in order of appearance,
+    /// one item per match. Regexes run on case folded text, so for example `ß` matches as
+    /// `ss`, and without diacritics when they are ignored.
+    pub fn find_all(&self, text: &str) -> Vec<&Keyword> {
+        let text = fold(text, self.ignore_diacritics);
+        let mut matches: Vec<(usize, usize, usize)> = Vec::new();
+
+        for mat in self.finder.find_overlapping_iter(&text) {
+            let (index, mode) = self.keyword_index_and_mode_by_pattern[mat.pattern()];
+            let before = text[..mat.start()].chars().next_back();
+            let after = text[mat.end()..].chars().next();
+            if is_word_boundary(before) && (mode == MatchMode::Prefix || is_word_boundary(after)) {
+                matches.push((mat.start(), mat.end(), index));
+            }
+        }
+
+        for (index, regex) in &self.regexes {
+            matches.extend(
+                regex
+                    .find_iter(&text)
+                    .map(|mat| (mat.start(), mat.end(), *index)),
+            );
+        }
+
+        // Overlapping synonyms of the same group only count once, the longest leftmost wins
+        matches
+            .sort_unstable_by_key(|(start, end, index)| (*start, std::cmp::Reverse(*end), *index));
+        let mut end_by_keyword: HashMap<usize, usize> = HashMap::new();
+        matches
+            .into_iter()
+            .filter(|(start, end, index)| {
+                let previous_end = end_by_keyword.entry(*index).or_insert(0);
+                if *start < *previous_end {
+                    return false;
+                }
+                *previous_end = *end;
+                true
+            })
+            .map(|(_, _, index)| &self.keywords[index])
+            .collect()
+    }
+}
+
+/// How scored keywords are acknowledged in a chat.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Acknowledgement {
+    /// A separate message for each match.
+    #[default]
+    Each,
+    /// One message listing all matches of a message.
+    Combined,
+    /// Like [`Acknowledgement::Combined`], but as a reply to the triggering message.
+    Reply,
+    /// An emoji reaction to the triggering message.
+    Reaction,
+    /// Points are scored without any acknowledgement.
+    Silent,
+}
+
+impl FromStr for Acknowledgement {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "erikseen" => Ok(Self::Each),
+            "yhdistetty" => Ok(Self::Combined),
+            "vastaus" => Ok(Self::Reply),
+            "reaktio" => Ok(Self::Reaction),
+            "hiljainen" => Ok(Self::Silent),
+            _ => Err(Error::UnknownAcknowledgement(s.into())),
+        }
+    }
+}
+
+impl fmt::Display for Acknowledgement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Each => "erikseen",
+            Self::Combined => "yhdistetty",
+            Self::Reply => "vastaus",
+            Self::Reaction => "reaktio",
+            Self::Silent => "hiljainen",
+        })
+    }
+}
+
+pub const DEFAULT_REACTION: &str = "👍";
+
+/// Renders an acknowledgement line for points scored, negative for penalties. Templates may
+/// contain `{sana}` and `{määrä}`.
+pub fn render_acknowledgement(template: Option<&str>, keyword: &str, points: i64) -> String {
+    match template {
+        Some(template) => template
+            .replace("{sana}", keyword)
+            .replace("{määrä}", &points.to_string()),
+        None if points == 1 => format!("Yksi (1) {} lisätty {0}-tilillesi", keyword),
+        None if points == -1 => format!("Yksi (1) {} vähennetty {0}-tililtäsi", keyword),
+        None if points < 0 => format!("{} {} vähennetty {1}-tililtäsi", -points, keyword),
+        None => format!("{} {} lisätty {1}-tilillesi", points, keyword),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub ignore_diacritics: bool,
+    pub max_points_per_message: Option<u64>,
+    pub cooldown: Option<Duration>,
+    pub daily_cap: Option<u64>,
+    pub log_rejected: bool,
+    pub ignore_forwarded: bool,
+    pub ignore_bots: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum Rejection {
+    #[error("at most {0} points per message")]
+    MessageLimit(u64),
+    #[error("cooldown of {} not elapsed", humantime::format_duration(*.0))]
+    Cooldown(Duration),
+    #[error("daily cap of {0} points reached")]
+    DailyCap(u64),
+}
+
+/// Applies the anti-spam limits of [`Options`] to keyword hits.
+#[derive(Debug, Default)]
+pub struct Limiter {
+    last_hit_by_chat_user_keyword: HashMap<(TelegramChatId, TelegramUserId, String), i64>,
+    day_and_points_by_chat_user: HashMap<(TelegramChatId, TelegramUserId), (i64, u64)>,
+    /// The day expired entries were last removed on
+    pruned_day: i64,
+    /// UTC offset of the day the daily cap resets on
+    utc_offset: i32,
+}
+
+impl Limiter {
+    pub fn new(utc_offset: i32) -> Self {
+        Self {
+            utc_offset,
+            ..Default::default()
+        }
+    }
+
+    /// Records a hit worth the keyword's weight in points if it is within limits.
+    /// `points_in_message` is the number of points already accepted from the same message.
+    pub fn try_score(
+        &mut self,
+        options: &Options,
+        keyword: &Keyword,
+        chat_id: TelegramChatId,
+        user_id: TelegramUserId,
+        timestamp: i64,
+        points_in_message: u64,
+    ) -> Result<(), Rejection> {
+        let points = u64::try_from(keyword.weight).unwrap_or(0);
+        if let Some(max) = options.max_points_per_message {
+            if points_in_message + points > max {
+                return Err(Rejection::MessageLimit(max));
+            }
+        }
+
+        let key = (chat_id, user_id, keyword.name.clone());
+        if let (Some(cooldown), Some(last)) = (
+            options.cooldown,
+            self.last_hit_by_chat_user_keyword.get(&key),
+        ) {
+            if timestamp - last < cooldown.as_secs().try_into().unwrap_or(i64::MAX) {
+                return Err(Rejection::Cooldown(cooldown));
+            }
+        }
+
+        let day = time_range::local_day(timestamp, self.utc_offset);
+        if day != self.pruned_day {
+            self.prune(options, timestamp, day);
+        }
+        let (points_day, day_points) = self
+            .day_and_points_by_chat_user
+            .entry((chat_id, user_id))
+            .or_insert((day, 0));
+        if *points_day != day {
+            *points_day = day;
+            *day_points = 0;
+        }
+        if let Some(cap) = options.daily_cap {
+            if *day_points + points > cap {
+                return Err(Rejection::DailyCap(cap));
+            }
+        }
+
+        *day_points += points;
+        self.last_hit_by_chat_user_keyword.insert(key, timestamp);
+        Ok(())
+    }
+
+    /// Removes hits whose cooldown has elapsed and points of earlier days.
+    fn prune(&mut self, options: &Options, timestamp: i64, day: i64) {
+        let cooldown = options
+            .cooldown
+            .map_or(0, |c| c.as_secs().try_into().unwrap_or(i64::MAX));
+        self.last_hit_by_chat_user_keyword
+            .retain(|_, last| timestamp - *last < cooldown);
+        self.day_and_points_by_chat_user
+            .retain(|_, (points_day, _)| *points_day == day);
+        self.pruned_day = day;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names<'a>(finder: &'a KeywordFinder, text: &str) -> Vec<&'a str> {
+        finder
+            .find_all(text)
+            .into_iter()
+            .map(|k| k.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn split_patterns_keeps_separators_in_regexes() {
+        let parts: Vec<&str> = split_patterns("a|/b|c/|d", '|').collect();
+        assert_eq!(parts, ["a", "/b|c/", "d"]);
+        let parts: Vec<&str> = split_patterns(r"/a\/|b/|c", '|').collect();
+        assert_eq!(parts, [r"/a\/|b/", "c"]);
+        let parts: Vec<&str> = split_patterns("x=/a,b/,y", ',').collect();
+        assert_eq!(parts, ["x=/a,b/", "y"]);
+    }
+
+    #[test]
+    fn split_patterns_keeps_empty_parts() {
+        let parts: Vec<&str> = split_patterns("a||b|", '|').collect();
+        assert_eq!(parts, ["a", "", "b", ""]);
+    }
+
+    #[test]
+    fn parse_plain_and_group() {
+        let keyword: Keyword = "kesko".parse().unwrap();
+        assert_eq!(keyword.name, "kesko");
+        assert_eq!(keyword.patterns[0].mode, MatchMode::Word);
+
+        let keyword: Keyword = "kauppa=kesko*|/k-?market/".parse().unwrap();
+        assert_eq!(keyword.name, "kauppa");
+        assert_eq!(keyword.patterns[0].mode, MatchMode::Prefix);
+        assert_eq!(keyword.patterns[1].mode, MatchMode::Regex);
+        assert_eq!(keyword.to_string(), "kauppa=kesko*|/k-?market/");
+    }
+
+    #[test]
+    fn parse_rejects_empty_and_invalid() {
+        assert!(matches!("".parse::<Keyword>(), Err(Error::NoPatterns(_))));
+        assert!(matches!(
+            "nimi=|".parse::<Keyword>(),
+            Err(Error::NoPatterns(_))
+        ));
+        assert!(matches!("*".parse::<Keyword>(), Err(Error::Empty)));
+        assert!(matches!("/(/".parse::<Keyword>(), Err(Error::Regex(_))));
+    }
+
+    #[test]
+    fn parse_weights() {
+        let keyword: Keyword = "kirosana=perkele|saatana=-1".parse().unwrap();
+        assert_eq!(keyword.name, "kirosana");
+        assert_eq!(keyword.patterns.len(), 2);
+        assert_eq!(keyword.weight, -1);
+        assert_eq!(keyword.to_string(), "kirosana=perkele|saatana=-1");
+
+        let keyword: Keyword = "kesko=kesko*=2".parse().unwrap();
+        assert_eq!((keyword.name.as_str(), keyword.weight), ("kesko", 2));
+        assert_eq!(keyword.to_string(), "kesko=kesko*=2");
+
+        let keyword: Keyword = "/a=b/=/a=b/=3".parse().unwrap();
+        assert_eq!(keyword.patterns[0].text, "a=b");
+        assert_eq!(keyword.weight, 3);
+
+        assert!(matches!("a=b=x".parse::<Keyword>(), Err(Error::Weight(_))));
+        assert!(matches!(
+            "a=b=1=2".parse::<Keyword>(),
+            Err(Error::TooManyFields(_))
+        ));
+    }
+
+    #[test]
+    fn colons_are_keyword_text() {
+        let keyword: Keyword = "klo 12:30".parse().unwrap();
+        assert_eq!(keyword.name, "klo 12:30");
+        assert_eq!(keyword.weight, 1);
+
+        let keyword: Keyword = "aika=klo 12:30|12.30".parse().unwrap();
+        assert_eq!(keyword.patterns[0].text, "klo 12:30");
+        assert_eq!(keyword.weight, 1);
+    }
+
+    #[test]
+    fn parse_list_splits_definitions() {
+        let keywords = parse_list("kesko, kauppa=lidl|/a,b/,").unwrap();
+        assert_eq!(keywords.len(), 2);
+        assert_eq!(keywords[1].patterns[1].text, "a,b");
+    }
+
+    #[test]
+    fn fold_case_and_diacritics() {
+        assert_eq!(fold("KESKO", false), "kesko");
+        assert_eq!(fold("Straße", false), "strasse");
+        assert_eq!(fold("Äiti", false), "äiti");
+        assert_eq!(fold("Äiti", true), "aiti");
+        // Decomposed input is composed when diacritics are kept
+        assert_eq!(fold("a\u{308}", false), "ä");
+    }
+
+    #[test]
+    fn finds_words_and_prefixes() {
+        let finder = KeywordFinder::new(parse_list("kesko, lidl*").unwrap(), false).unwrap();
+        assert_eq!(names(&finder, "Kesko ja lidlissä"), ["kesko", "lidl"]);
+        assert!(names(&finder, "keskolla").is_empty());
+    }
+
+    #[test]
+    fn regex_matches_with_ignored_diacritics() {
+        let keywords = parse_list("/pää/").unwrap();
+        let finder = KeywordFinder::new(keywords.clone(), true).unwrap();
+        assert_eq!(names(&finder, "Pää"), ["pää"]);
+        let finder = KeywordFinder::new(keywords, false).unwrap();
+        assert_eq!(names(&finder, "PÄÄ"), ["pää"]);
+    }
+
+    #[test]
+    fn overlapping_synonyms_count_once() {
+        let finder =
+            KeywordFinder::new(parse_list("kauppa=k-market|market").unwrap(), false).unwrap();
+        assert_eq!(names(&finder, "k-market"), ["kauppa"]);
+        assert_eq!(names(&finder, "k-market ja market"), ["kauppa", "kauppa"]);
+    }
+
+    #[test]
+    fn limiter_resets_daily_cap_and_prunes() {
+        let options = Options {
+            cooldown: Some(Duration::from_secs(60)),
+            daily_cap: Some(1),
+            ..Default::default()
+        };
+        let (a, b) = ("a".parse().unwrap(), "b".parse().unwrap());
+        let mut limiter = Limiter::new(2 * 60 * 60);
+        assert!(limiter.try_score(&options, &a, 1, 1, -7200, 0).is_ok());
+        assert!(limiter.try_score(&options, &a, 1, 2, 10, 0).is_ok());
+        assert!(matches!(
+            limiter.try_score(&options, &b, 1, 1, 20, 0),
+            Err(Rejection::DailyCap(1))
+        ));
+        assert!(limiter
+            .try_score(&options, &a, 1, 1, 86400 - 7200, 0)
+            .is_ok());
+        assert_eq!(limiter.last_hit_by_chat_user_keyword.len(), 1);
+        assert_eq!(limiter.day_and_points_by_chat_user.len(), 1);
+    }
+
+    #[test]
+    fn limits_count_points() {
+        let options = Options {
+            max_points_per_message: Some(4),
+            daily_cap: Some(6),
+            ..Default::default()
+        };
+        let (kesko, market) = (
+            "kesko=kesko=5".parse().unwrap(),
+            "market=market=2".parse().unwrap(),
+        );
+        let mut limiter = Limiter::new(0);
+        assert!(matches!(
+            limiter.try_score(&options, &kesko, 1, 1, 0, 0),
+            Err(Rejection::MessageLimit(4))
+        ));
+        assert!(limiter.try_score(&options, &market, 1, 1, 0, 0).is_ok());
+        assert!(limiter.try_score(&options, &market, 1, 1, 0, 2).is_ok());
+        assert!(matches!(
+            limiter.try_score(&options, &market, 1, 1, 0, 4),
+            Err(Rejection::MessageLimit(4))
+        ));
+        assert!(limiter.try_score(&options, &market, 1, 1, 10, 0).is_ok());
+        assert!(matches!(
+            limiter.try_score(&options, &market, 1, 1, 20, 0),
+            Err(Rejection::DailyCap(6))
+        ));
+    }
+
+    #[test]
+    fn acknowledgement_uses_points() {
+        assert_eq!(
+            render_acknowledgement(None, "kesko", 3),
+            "3 kesko lisätty kesko-tilillesi"
+        );
+        assert_eq!(
+            render_acknowledgement(None, "kirosana", -1),
+            "Yksi (1) kirosana vähennetty kirosana-tililtäsi"
+        );
+        assert_eq!(
+            render_acknowledgement(Some("{määrä} {sana}"), "kirosana", -2),
+            "-2 kirosana"
+        );
+    }
+}
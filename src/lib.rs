@@ -1,8 +1,11 @@
 pub mod commands;
+pub mod keywords;
+pub mod message_kinds;
 pub mod metadata_store;
+pub mod records;
 
-use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
-use commands::CommandInvocation;
+use commands::{CommandContext, CommandInvocation};
+use keywords::{Acknowledgement, Keyword, KeywordFinder, Limiter};
 use metadata_store::MetadataStore;
 use serde_json::json;
 use std::{
@@ -22,23 +25,64 @@ pub enum Error {
     #[error("Failed to parse request body")]
     JsonConversion(#[from] io::Error),
     #[error("Request failed with {0}")]
-    Request(#[from] ureq::Error),
+    Request(Box<ureq::Error>),
     #[error("Failed to store message metadata")]
     MetadataStore(#[from] metadata_store::Error),
 }
 
-pub type TelegramUserId = i32;
+impl From<ureq::Error> for Error {
+    fn from(e: ureq::Error) -> Self {
+        Self::Request(Box::new(e))
+    }
+}
+
+/// A user, or a chat for messages sent on behalf of a chat
+pub type TelegramUserId = i64;
 pub type TelegramChatId = i64;
 pub type TelegramMessageId = i32;
+/// A forum topic, identified by the message that created it
+pub type TelegramThreadId = i32;
+
+fn is_command(message: &serde_json::Value) -> bool {
+    message["entities"]
+        .as_array()
+        .is_some_and(|entities| entities.iter().any(|e| e["type"] == json!("bot_command")))
+}
+
+/// Which messages are counted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CountingOptions {
+    /// Don't count messages sent by other bots
+    pub ignore_bots: bool,
+    /// Count an album of photos or videos as one message
+    pub count_albums_once: bool,
+}
+
+/// Returns the forum topic of a message. Messages in the General topic have none.
+fn thread_id(message: &serde_json::Value) -> Option<TelegramThreadId> {
+    if message["is_topic_message"] != json!(true) {
+        return None;
+    }
+    message["message_thread_id"].as_i64()?.try_into().ok()
+}
 
 pub struct StatsBot {
     timeout: Duration,
     api_url_get_updates: String,
     api_url_send_message: String,
     api_url_edit_message_text: String,
+    api_url_get_chat_member: String,
+    api_url_set_message_reaction: String,
+    #[cfg(feature = "png")]
+    api_url_send_photo: String,
     metadata_store: MetadataStore,
-    keywords: Vec<String>,
-    keyword_finder: AhoCorasick,
+    default_keywords: Vec<Keyword>,
+    keyword_options: keywords::Options,
+    keyword_finders_by_chat: HashMap<TelegramChatId, KeywordFinder>,
+    keyword_limiter: Limiter,
+    utc_offset: i32,
+    counting_options: CountingOptions,
+    last_media_group_by_chat: HashMap<TelegramChatId, String>,
     last_command_invocation_and_message_id_by_chat:
         HashMap<TelegramChatId, (CommandInvocation, TelegramMessageId)>,
     messages_after_last_post_by_chat: HashMap<TelegramChatId, usize>,
@@ -48,20 +92,30 @@ impl StatsBot {
     pub fn new(
         api_url: &str,
         timeout: Duration,
-        metadata_store: MetadataStore,
-        keywords: Vec<String>,
+        mut metadata_store: MetadataStore,
+        default_keywords: Vec<Keyword>,
+        keyword_options: keywords::Options,
+        utc_offset: i32,
+        counting_options: CountingOptions,
     ) -> Self {
-        let keyword_finder = AhoCorasickBuilder::new()
-            .ascii_case_insensitive(true)
-            .build(&keywords);
+        metadata_store.set_utc_offset(utc_offset);
         Self {
             timeout,
             api_url_get_updates: format!("{}/getUpdates", api_url),
             api_url_send_message: format!("{}/sendMessage", api_url),
             api_url_edit_message_text: format!("{}/editMessageText", api_url),
+            api_url_get_chat_member: format!("{}/getChatMember", api_url),
+            api_url_set_message_reaction: format!("{}/setMessageReaction", api_url),
+            #[cfg(feature = "png")]
+            api_url_send_photo: format!("{}/sendPhoto", api_url),
             metadata_store,
-            keywords,
-            keyword_finder,
+            default_keywords,
+            keyword_options,
+            keyword_finders_by_chat: HashMap::new(),
+            keyword_limiter: Limiter::new(utc_offset),
+            utc_offset,
+            counting_options,
+            last_media_group_by_chat: HashMap::new(),
             last_command_invocation_and_message_id_by_chat: HashMap::new(),
             messages_after_last_post_by_chat: HashMap::new(),
         }
@@ -70,12 +124,21 @@ impl StatsBot {
     fn send_message(
         &self,
         chat_id: TelegramChatId,
+        thread_id: Option<TelegramThreadId>,
         text: &str,
+        reply_to: Option<TelegramMessageId>,
     ) -> Result<TelegramMessageId, Error> {
-        let response = ureq::post(&self.api_url_send_message).send_json(json!({
+        let mut params = json!({
                 "chat_id": chat_id,
                 "text": text
-        }));
+        });
+        if let Some(thread_id) = thread_id {
+            params["message_thread_id"] = json!(thread_id);
+        }
+        if let Some(message_id) = reply_to {
+            params["reply_to_message_id"] = json!(message_id);
+        }
+        let response = ureq::post(&self.api_url_send_message).send_json(params);
 
         match response {
             Ok(response) => {
@@ -94,6 +157,59 @@ impl StatsBot {
         }
     }
 
+    #[cfg(feature = "png")]
+    fn send_photo(
+        &self,
+        chat_id: TelegramChatId,
+        thread_id: Option<TelegramThreadId>,
+        png: &[u8],
+        caption: &str,
+    ) -> Result<TelegramMessageId, Error> {
+        const BOUNDARY: &str = "mfj-photo-boundary";
+
+        let mut body = Vec::new();
+        let mut fields = vec![
+            ("chat_id", chat_id.to_string()),
+            ("caption", caption.into()),
+        ];
+        if let Some(thread_id) = thread_id {
+            fields.push(("message_thread_id", thread_id.to_string()));
+        }
+        for (name, value) in fields {
+            body.extend_from_slice(
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                    BOUNDARY, name, value
+                )
+                .as_bytes(),
+            );
+        }
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"photo\"; filename=\"image.png\"\r\n\
+                Content-Type: image/png\r\n\r\n",
+                BOUNDARY
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(png);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+        let response: serde_json::Value = ureq::post(&self.api_url_send_photo)
+            .set(
+                "Content-Type",
+                &format!("multipart/form-data; boundary={}", BOUNDARY),
+            )
+            .send_bytes(&body)?
+            .into_json()?;
+
+        Ok(response["result"]["message_id"]
+            .as_i64()
+            .unwrap()
+            .try_into()
+            .unwrap())
+    }
+
     fn update_message(
         &self,
         chat_id: TelegramChatId,
@@ -109,15 +225,154 @@ impl StatsBot {
         Ok(())
     }
 
-    fn store_user_name(&mut self, user_id: TelegramUserId, user: &serde_json::Value) {
-        let mut user_name = user["first_name"].as_str().unwrap().to_string();
+    fn set_message_reaction(
+        &self,
+        chat_id: TelegramChatId,
+        message_id: TelegramMessageId,
+        emoji: &str,
+    ) -> Result<(), Error> {
+        ureq::post(&self.api_url_set_message_reaction).send_json(json!({
+                "chat_id": chat_id,
+                "message_id": message_id,
+                "reaction": [{ "type": "emoji", "emoji": emoji }]
+        }))?;
 
-        if let Some(last_name) = user.get("last_name") {
-            user_name.push_str(&format!(" {}", last_name.as_str().unwrap()));
+        Ok(())
+    }
+
+    fn acknowledge_keywords(
+        &self,
+        chat_id: TelegramChatId,
+        thread_id: Option<TelegramThreadId>,
+        message_id: TelegramMessageId,
+        hits: &[(String, i64)],
+    ) -> Result<(), Error> {
+        if hits.is_empty() {
+            return Ok(());
         }
 
-        if let Some(username) = user.get("username") {
-            user_name.push_str(&format!(" ({})", username.as_str().unwrap()));
+        let settings = self.metadata_store.get_chat_settings(chat_id);
+        let template = settings.keyword_template.as_deref();
+
+        // Sum points of repeated keywords in order of first appearance
+        let mut totals: Vec<(&str, i64)> = Vec::new();
+        for (name, points) in hits {
+            match totals.iter_mut().find(|(n, _)| n == name) {
+                Some((_, total)) => *total += points,
+                None => totals.push((name, *points)),
+            }
+        }
+        let combined = || {
+            totals
+                .iter()
+                .map(|(name, total)| keywords::render_acknowledgement(template, name, *total))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        match settings.keyword_acknowledgement {
+            Acknowledgement::Each => {
+                for (name, points) in hits {
+                    let text = keywords::render_acknowledgement(template, name, *points);
+                    self.send_message(chat_id, thread_id, &text, None)?;
+                }
+            }
+            Acknowledgement::Combined => {
+                self.send_message(chat_id, thread_id, &combined(), None)?;
+            }
+            Acknowledgement::Reply => {
+                self.send_message(chat_id, thread_id, &combined(), Some(message_id))?;
+            }
+            Acknowledgement::Reaction => {
+                let emoji = settings
+                    .keyword_reaction
+                    .as_deref()
+                    .unwrap_or(keywords::DEFAULT_REACTION);
+                // Only some emoji are allowed as reactions, so don't fail on a bad setting
+                self.set_message_reaction(chat_id, message_id, emoji)
+                    .unwrap_or_else(|e| log::error!("Failed to react with {}: {}", emoji, e));
+            }
+            Acknowledgement::Silent => {}
+        }
+
+        Ok(())
+    }
+
+    /// Sends a heatmap image, or the reason it can't be drawn. Returns false if the image
+    /// was rejected and the text heatmap should be sent instead.
+    #[cfg(feature = "png")]
+    fn send_heatmap_image(&self, command: &str, context: &CommandContext) -> Result<bool, Error> {
+        let (chat_id, thread_id) = (context.chat_id, context.thread_id);
+        match commands::command_heatmap::render_png(command, context, &self.metadata_store) {
+            Ok((png, title)) => match self.send_photo(chat_id, thread_id, &png, &title) {
+                Ok(_) => Ok(true),
+                Err(e) => {
+                    log::error!("Failed to send heatmap image, sending text instead: {}", e);
+                    Ok(false)
+                }
+            },
+            Err(text) => {
+                self.send_message(chat_id, thread_id, &text, None)?;
+                Ok(true)
+            }
+        }
+    }
+
+    fn is_chat_admin(
+        &self,
+        chat_id: TelegramChatId,
+        user_id: TelegramUserId,
+    ) -> Result<bool, Error> {
+        let response: serde_json::Value = ureq::post(&self.api_url_get_chat_member)
+            .send_json(json!({
+                "chat_id": chat_id,
+                "user_id": user_id
+            }))?
+            .into_json()?;
+
+        Ok(matches!(
+            response["result"]["status"].as_str(),
+            Some("creator" | "administrator")
+        ))
+    }
+
+    fn keyword_finder(&mut self, chat_id: TelegramChatId) -> &KeywordFinder {
+        let metadata_store = &self.metadata_store;
+        let default_keywords = &self.default_keywords;
+        let ignore_diacritics = self.keyword_options.ignore_diacritics;
+        self.keyword_finders_by_chat
+            .entry(chat_id)
+            .or_insert_with(|| {
+                let keywords = metadata_store
+                    .get_chat_keywords(chat_id)
+                    .unwrap_or(default_keywords)
+                    .to_vec();
+                KeywordFinder::new(keywords, ignore_diacritics).unwrap_or_else(|e| {
+                    log::error!(
+                        "Failed to build keyword matcher for chat {}: {}",
+                        chat_id,
+                        e
+                    );
+                    KeywordFinder::new(Vec::new(), ignore_diacritics).unwrap()
+                })
+            })
+    }
+
+    fn store_user_name(
+        &mut self,
+        user_id: TelegramUserId,
+        user: &serde_json::Value,
+        timestamp: i64,
+    ) {
+        let mut user_name = user["first_name"].as_str().unwrap_or_default().to_string();
+
+        if let Some(last_name) = user["last_name"].as_str() {
+            user_name.push_str(&format!(" {}", last_name));
+        }
+
+        if let Some(username) = user["username"].as_str() {
+            user_name.push_str(&format!(" ({})", username));
+            self.metadata_store.add_username(user_id, username);
         }
 
         // Remove cheeky Right to Left codes from names (TODO more sanitization)
@@ -126,34 +381,365 @@ impl StatsBot {
             .filter(|c| *c as u32 != 0x200f_u32)
             .collect();
 
-        self.metadata_store.add_user_name(user_id, user_name);
+        self.metadata_store
+            .add_user_name(user_id, user_name, timestamp);
+    }
+
+    /// Scores keywords in a new or edited message. Points from a previous version of the
+    /// message are kept if the keyword is still present and removed otherwise, so editing
+    /// can't be used to score the same message again.
+    fn score_keywords(
+        &mut self,
+        message: &serde_json::Value,
+        chat_id: TelegramChatId,
+        user_id: TelegramUserId,
+        message_id: TelegramMessageId,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        let text = match message["text"].as_str().or(message["caption"].as_str()) {
+            Some(text) => text,
+            None => return Ok(()),
+        };
+        if self.keyword_options.ignore_forwarded
+            && (message.get("forward_origin").is_some() || message.get("forward_date").is_some())
+        {
+            return Ok(());
+        }
+        if self.keyword_options.ignore_bots && message["from"]["is_bot"] == json!(true) {
+            return Ok(());
+        }
+
+        // Hits are recorded at the original message time, also when scored from an edit
+        let date = message["date"].as_i64().unwrap();
+        let found: Vec<Keyword> = self
+            .keyword_finder(chat_id)
+            .find_all(text)
+            .into_iter()
+            .cloned()
+            .collect();
+        let mut previous = self
+            .metadata_store
+            .get_message_keywords(chat_id, message_id)
+            .to_vec();
+
+        let mut scored = Vec::new();
+        let mut added = Vec::new();
+        let mut points_in_message = 0;
+        for keyword in found {
+            let (name, weight) = (keyword.name.clone(), keyword.weight);
+            if let Some(i) = previous.iter().position(|n| *n == name) {
+                previous.remove(i);
+                points_in_message += u64::try_from(weight).unwrap_or(0);
+                scored.push(name);
+                continue;
+            }
+
+            // Penalties always apply and don't use up the limits
+            if weight > 0 {
+                if let Err(rejection) = self.keyword_limiter.try_score(
+                    &self.keyword_options,
+                    &keyword,
+                    chat_id,
+                    user_id,
+                    timestamp,
+                    points_in_message,
+                ) {
+                    if self.keyword_options.log_rejected {
+                        log::info!(
+                            "Rejected keyword {} from {} in chat {}: {}",
+                            name,
+                            user_id,
+                            chat_id,
+                            rejection
+                        );
+                    }
+                    continue;
+                }
+                points_in_message += weight as u64;
+            }
+
+            self.metadata_store
+                .add_keyword_hit(&name, chat_id, user_id, date, weight)?;
+            scored.push(name.clone());
+            added.push((name, weight));
+        }
+
+        // Keywords edited out of the message
+        for name in previous {
+            self.metadata_store
+                .remove_keyword_hit(&name, chat_id, user_id, date)?;
+        }
+
+        self.metadata_store
+            .set_message_keywords(chat_id, message_id, date, scored)?;
+        self.acknowledge_keywords(chat_id, thread_id(message), message_id, &added)
+    }
+
+    /// Returns who a message is attributed to. Messages from anonymous admins and linked
+    /// channels are attributed to the sending chat. `None` for messages without a sender and
+    /// for bots when they are ignored.
+    fn sender_id(&self, message: &serde_json::Value) -> Option<TelegramUserId> {
+        if let Some(sender_chat_id) = message["sender_chat"]["id"].as_i64() {
+            return Some(sender_chat_id);
+        }
+        let user = &message["from"];
+        if !self.is_counted_user(user) {
+            return None;
+        }
+        user["id"].as_i64()
+    }
+
+    /// Bots are counted unless configured otherwise.
+    fn is_counted_user(&self, user: &serde_json::Value) -> bool {
+        user["is_bot"] != json!(true) || !self.counting_options.ignore_bots
+    }
+
+    fn store_sender_name(&mut self, user_id: TelegramUserId, message: &serde_json::Value) {
+        let timestamp = message["date"].as_i64().unwrap_or_default();
+        match message["sender_chat"]["title"].as_str() {
+            Some(title) => {
+                self.metadata_store
+                    .add_user_name(user_id, title.replace('\u{200f}', ""), timestamp)
+            }
+            None => self.store_user_name(user_id, &message["from"], timestamp),
+        }
+    }
+
+    /// Records who the message replies to and mentions.
+    fn store_interactions(
+        &mut self,
+        message: &serde_json::Value,
+        chat_id: TelegramChatId,
+        user_id: TelegramUserId,
+    ) -> Result<(), Error> {
+        // In forums every message replies to the topic creation message
+        let reply_to = &message["reply_to_message"];
+        if reply_to.get("forum_topic_created").is_none() {
+            if let Some(to_user_id) = self.sender_id(reply_to) {
+                if to_user_id != user_id {
+                    self.metadata_store
+                        .add_reply(chat_id, user_id, to_user_id)?;
+                }
+            }
+        }
+
+        let (text, entities) = match (message["text"].as_str(), message["entities"].as_array()) {
+            (Some(text), Some(entities)) => (text, entities),
+            _ => match (
+                message["caption"].as_str(),
+                message["caption_entities"].as_array(),
+            ) {
+                (Some(text), Some(entities)) => (text, entities),
+                _ => return Ok(()),
+            },
+        };
+        // Entity offsets and lengths are in UTF-16 code units
+        let utf16: Vec<u16> = text.encode_utf16().collect();
+        for entity in entities {
+            let to_user_id = match entity["type"].as_str() {
+                Some("text_mention") => entity["user"]["id"].as_i64(),
+                Some("mention") => {
+                    let offset = entity["offset"].as_u64().unwrap_or(0) as usize;
+                    let length = entity["length"].as_u64().unwrap_or(0) as usize;
+                    utf16
+                        .get(offset..offset + length)
+                        .map(String::from_utf16_lossy)
+                        .and_then(|username| self.metadata_store.find_user_by_username(&username))
+                }
+                _ => None,
+            };
+            if let Some(to_user_id) = to_user_id.filter(|id| *id != user_id) {
+                self.metadata_store
+                    .add_mention(chat_id, user_id, to_user_id)?;
+            }
+        }
+
+        Ok(())
     }
 
     fn process_updates(&mut self, updates: &[serde_json::Value]) -> Result<(), Error> {
         'update_loop: for update in updates {
             log::trace!("{}", update);
 
+            if let Some(message) = update.get("edited_message") {
+                let chat_id: TelegramChatId = message["chat"]["id"].as_i64().unwrap();
+                let Some(user_id) = self.sender_id(message) else {
+                    continue;
+                };
+                let message_id: TelegramMessageId =
+                    message["message_id"].as_i64().unwrap().try_into().unwrap();
+                let edit_timestamp = message["edit_date"].as_i64().unwrap();
+
+                // Commands never score, also when edited
+                if !is_command(message)
+                    && self.metadata_store.is_message_keywords_tracked(
+                        message["date"].as_i64().unwrap(),
+                        edit_timestamp,
+                    )
+                {
+                    self.score_keywords(message, chat_id, user_id, message_id, edit_timestamp)?;
+                }
+            }
+
+            if let Some(reaction) = update.get("message_reaction") {
+                let chat_id: TelegramChatId = reaction["chat"]["id"].as_i64().unwrap();
+                let message_id: TelegramMessageId =
+                    reaction["message_id"].as_i64().unwrap().try_into().unwrap();
+                // Anonymous admins react on behalf of the chat
+                if let Some(user_id) = reaction["user"]["id"]
+                    .as_i64()
+                    .or(reaction["actor_chat"]["id"].as_i64())
+                {
+                    let count = |field: &str| reaction[field].as_array().map_or(0, Vec::len) as i64;
+                    self.metadata_store.add_reactions(
+                        chat_id,
+                        message_id,
+                        user_id,
+                        count("new_reaction") - count("old_reaction"),
+                    )?;
+                }
+            }
+
+            if let Some(reaction_count) = update.get("message_reaction_count") {
+                let chat_id: TelegramChatId = reaction_count["chat"]["id"].as_i64().unwrap();
+                let message_id: TelegramMessageId = reaction_count["message_id"]
+                    .as_i64()
+                    .unwrap()
+                    .try_into()
+                    .unwrap();
+                let total = reaction_count["reactions"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|r| r["total_count"].as_u64())
+                    .sum();
+                self.metadata_store
+                    .set_anonymous_reactions(chat_id, message_id, total)?;
+            }
+
             if let Some(message) = update.get("message") {
                 let chat_id: TelegramChatId = message["chat"]["id"].as_i64().unwrap();
-                let user = &message["from"];
-                let user_id: TelegramUserId = user["id"].as_i64().unwrap().try_into().unwrap();
                 let timestamp = message["date"].as_i64().unwrap();
 
-                self.store_user_name(user_id, user);
+                // Record topic names, joins and leaves, and do not count them as messages
+                let topic = message
+                    .get("forum_topic_created")
+                    .or(message.get("forum_topic_edited"));
+                if let Some(topic) = topic {
+                    if let (Some(thread_id), Some(name)) =
+                        (thread_id(message), topic["name"].as_str())
+                    {
+                        self.metadata_store
+                            .set_topic_name(chat_id, thread_id, name)?;
+                    }
+                    continue 'update_loop;
+                }
+                if let Some(members) = message["new_chat_members"].as_array() {
+                    for member in members {
+                        let Some(member_id) = member["id"].as_i64() else {
+                            continue;
+                        };
+                        if !self.is_counted_user(member) {
+                            continue;
+                        }
+                        self.store_user_name(member_id, member, timestamp);
+                        self.metadata_store
+                            .add_membership_event(chat_id, member_id, timestamp, true)?;
+                    }
+                    continue 'update_loop;
+                }
+                if let Some(member) = message.get("left_chat_member") {
+                    if let Some(member_id) = member["id"]
+                        .as_i64()
+                        .filter(|_| self.is_counted_user(member))
+                    {
+                        self.store_user_name(member_id, member, timestamp);
+                        self.metadata_store
+                            .add_membership_event(chat_id, member_id, timestamp, false)?;
+                    }
+                    continue 'update_loop;
+                }
 
-                if let Some(entities) = message.get("entities") {
-                    for entity in entities.as_array().unwrap() {
+                let Some(user_id) = self.sender_id(message) else {
+                    log::debug!("Ignoring message without a counted sender in {}", chat_id);
+                    continue;
+                };
+                let message_id: TelegramMessageId =
+                    message["message_id"].as_i64().unwrap().try_into().unwrap();
+
+                self.store_sender_name(user_id, message);
+
+                if let Some(entities) = message["entities"].as_array() {
+                    for entity in entities {
                         if entity["type"] == json!("bot_command") {
-                            let command = message["text"].as_str().unwrap();
+                            let Some(command) = message["text"].as_str() else {
+                                continue;
+                            };
                             log::info!("Received command: '{}' from {}", command, user_id);
 
                             // Get the command part of a command message and pattern match it
                             // Won't panic, always contains at least a '/'
                             let word = command.split_whitespace().next().unwrap();
                             let word = word.split('@').next().unwrap_or(word);
+
+                            let context = CommandContext {
+                                chat_id,
+                                user_id,
+                                thread_id: thread_id(message),
+                                utc_offset: self.utc_offset,
+                            };
+
+                            #[cfg(feature = "png")]
+                            if matches!(word, "/lämpökartta" | "/lampokartta")
+                                && commands::arguments(command).starts_with("kuva")
+                                && self.send_heatmap_image(command, &context)?
+                            {
+                                continue 'update_loop; // Do not count bot commands
+                            }
+
+                            if word == "/avainsana" {
+                                // Listing is allowed for everyone, so only check admin status
+                                // for modifications
+                                let is_admin = commands::command_keywords::is_modification(command)
+                                    && (message["chat"]["type"] == json!("private")
+                                        || user_id == chat_id
+                                        || self.is_chat_admin(chat_id, user_id).unwrap_or_else(
+                                            |e| {
+                                                log::error!("Failed to check admin status: {}", e);
+                                                false
+                                            },
+                                        ));
+                                let (text, changed) = commands::command_keywords::run(
+                                    command,
+                                    chat_id,
+                                    &mut self.metadata_store,
+                                    &self.default_keywords,
+                                    is_admin,
+                                )?;
+                                if changed {
+                                    self.keyword_finders_by_chat.remove(&chat_id);
+                                }
+                                self.send_message(chat_id, context.thread_id, &text, None)?;
+
+                                continue 'update_loop; // Do not count bot commands
+                            }
+
                             let procedure: Option<commands::CommandProcedure> = match word {
                                 "/tilasto" => Some(commands::command_stats::render),
+                                "/jäsenet" | "/jasenet" => Some(commands::command_members::render),
+                                "/nimet" => Some(commands::command_names::render),
                                 "/pisteet" => Some(commands::command_scores::render),
+                                "/minä" | "/mina" => Some(commands::command_me::render),
+                                "/lämpökartta" | "/lampokartta" => {
+                                    Some(commands::command_heatmap::render)
+                                }
+                                "/ennätykset" | "/ennatykset" => {
+                                    Some(commands::command_records::render)
+                                }
+                                "/keskustelut" => Some(commands::command_sessions::render),
+                                "/emojit" => Some(commands::command_emoji::render),
+                                "/reaktiot" => Some(commands::command_reactions::render),
+                                "/vuorovaikutus" => Some(commands::command_interactions::render),
                                 _ => None,
                             };
 
@@ -161,14 +747,15 @@ impl StatsBot {
                                 let invocation = CommandInvocation {
                                     procedure,
                                     command_string: command.to_string(),
-                                    chat_id,
+                                    context,
                                 };
 
                                 // Run command
                                 let text = invocation.run(&mut self.metadata_store);
 
                                 // Send result
-                                let message_id = self.send_message(chat_id, &text)?;
+                                let message_id =
+                                    self.send_message(chat_id, context.thread_id, &text, None)?;
 
                                 // Store last command invocation and response ids
                                 self.last_command_invocation_and_message_id_by_chat
@@ -181,27 +768,24 @@ impl StatsBot {
                     }
                 }
 
-                // Check keywords
-                if let Some(text) = message["text"].as_str() {
-                    for mat in self.keyword_finder.find_iter(text) {
-                        if text[..mat.start()]
-                            .chars()
-                            .last()
-                            .map_or(true, |c| c.is_whitespace())
-                        {
-                            self.metadata_store.add_keyword_point(
-                                &self.keywords[mat.pattern()],
-                                chat_id,
-                                user_id,
-                            )?;
-                            self.send_message(
-                                chat_id,
-                                &format!(
-                                    "Yksi (1) {} lisätty {0}-tilillesi",
-                                    self.keywords[mat.pattern()]
-                                ),
-                            )?;
-                        }
+                self.score_keywords(message, chat_id, user_id, message_id, timestamp)?;
+                self.store_interactions(message, chat_id, user_id)?;
+
+                self.metadata_store
+                    .set_message_author(chat_id, message_id, user_id, timestamp)?;
+
+                // Albums arrive as one message per item. Later items still add their
+                // captions, emoji and kinds, but not another message.
+                let mut album_counted = false;
+                if self.counting_options.count_albums_once {
+                    if let Some(media_group_id) = message["media_group_id"].as_str() {
+                        album_counted = self
+                            .last_media_group_by_chat
+                            .get(&chat_id)
+                            .map(String::as_str)
+                            == Some(media_group_id);
+                        self.last_media_group_by_chat
+                            .insert(chat_id, media_group_id.into());
                     }
                 }
 
@@ -211,10 +795,41 @@ impl StatsBot {
                     .get(&chat_id)
                     .copied()
                     .unwrap_or(0);
-                self.messages_after_last_post_by_chat
-                    .insert(chat_id, count + 1);
-                self.metadata_store
-                    .add_message(chat_id, user_id, timestamp)?;
+                if !album_counted {
+                    self.messages_after_last_post_by_chat
+                        .insert(chat_id, count + 1);
+                    self.metadata_store.add_message(
+                        chat_id,
+                        thread_id(message),
+                        user_id,
+                        timestamp,
+                    )?;
+                }
+                self.metadata_store.add_message_kinds(
+                    chat_id,
+                    thread_id(message),
+                    user_id,
+                    &message_kinds::message_kinds(message),
+                )?;
+                if let Some(text) = message["text"].as_str().or(message["caption"].as_str()) {
+                    self.metadata_store.add_text_length(
+                        chat_id,
+                        thread_id(message),
+                        user_id,
+                        text,
+                    )?;
+                    for emoji in message_kinds::emojis(text) {
+                        self.metadata_store.add_emoji(chat_id, user_id, emoji)?;
+                    }
+                }
+                let sticker = &message["sticker"];
+                if let Some(emoji) = sticker["emoji"].as_str() {
+                    self.metadata_store.add_emoji(chat_id, user_id, emoji)?;
+                }
+                if let Some(set_name) = sticker["set_name"].as_str() {
+                    self.metadata_store
+                        .add_sticker_set(chat_id, user_id, set_name)?;
+                }
 
                 // Update previous response with new invocation
                 log::debug!("messages_after_last_post_by_chat[{}] = {}", chat_id, count);
@@ -244,7 +859,15 @@ impl StatsBot {
     }
 
     pub fn poll(&mut self, running: Arc<AtomicBool>) -> Result<(), Error> {
-        let mut params_get_updates = json!({ "timeout": self.timeout.as_secs() });
+        let mut params_get_updates = json!({
+            "timeout": self.timeout.as_secs(),
+            "allowed_updates": [
+                "message",
+                "edited_message",
+                "message_reaction",
+                "message_reaction_count",
+            ],
+        });
 
         log::info!(
             "Starting polling, timeout {}",
@@ -298,3 +921,43 @@ impl StatsBot {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bot() -> StatsBot {
+        StatsBot::new(
+            "http://localhost",
+            Duration::from_secs(1),
+            MetadataStore::in_memory(),
+            Vec::new(),
+            Default::default(),
+            0,
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn duplicate_names_disambiguated_per_chat() {
+        let mut bot = bot();
+        let users = [
+            (1, 10, json!({ "first_name": "Matti" })),
+            (2, 10, json!({ "first_name": "Matti", "username": "mv" })),
+            (3, 10, json!({ "first_name": "Matti" })),
+            (4, 20, json!({ "first_name": "Matti" })),
+        ];
+        for (user_id, chat_id, user) in users {
+            bot.store_user_name(user_id, &user, 100);
+            bot.metadata_store
+                .add_message(chat_id, None, user_id, 100)
+                .unwrap();
+        }
+
+        let name = |chat_id, user_id| bot.metadata_store.get_user_name(chat_id, user_id);
+        assert_eq!(name(10, 1), Some("Matti #1"));
+        assert_eq!(name(10, 2), Some("Matti (mv)"));
+        assert_eq!(name(10, 3), Some("Matti #3"));
+        assert_eq!(name(20, 4), Some("Matti"));
+    }
+}
@@ -20,6 +20,17 @@ impl std::str::FromStr for MyDuration {
     }
 }
 
+struct UtcOffset(i32);
+
+impl std::str::FromStr for UtcOffset {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        mfj::commands::time_range::parse_utc_offset(s)
+            .map(Self)
+            .ok_or_else(|| format!("invalid UTC offset {}", s))
+    }
+}
+
 #[derive(FromArgs)]
 #[argh(description = "A telegram bot")]
 struct MfjOptions {
@@ -31,6 +42,50 @@ struct MfjOptions {
         default = "MyDuration(Duration::from_secs(60 * 30))"
     )]
     write_interval: MyDuration,
+    #[argh(
+        switch,
+        description = "match keywords regardless of diacritics (ä matches a)"
+    )]
+    ignore_diacritics: bool,
+    #[argh(
+        option,
+        description = "maximum number of keyword points scored from one message"
+    )]
+    keyword_max_per_message: Option<u64>,
+    #[argh(
+        option,
+        description = "minimum time between points for the same keyword and user (example: '10 min')"
+    )]
+    keyword_cooldown: Option<MyDuration>,
+    #[argh(
+        option,
+        description = "maximum number of keyword points per user per day"
+    )]
+    keyword_daily_cap: Option<u64>,
+    #[argh(switch, description = "log keyword hits rejected by the limits")]
+    log_rejected_keywords: bool,
+    #[argh(switch, description = "don't score keywords in forwarded messages")]
+    ignore_forwarded_keywords: bool,
+    #[argh(switch, description = "don't score keywords in messages sent by bots")]
+    ignore_bot_keywords: bool,
+    #[argh(switch, description = "don't count messages sent by other bots")]
+    ignore_bots: bool,
+    #[argh(
+        switch,
+        description = "count an album of photos or videos as one message"
+    )]
+    count_albums_once: bool,
+    #[argh(
+        option,
+        description = "UTC offset for calendar days and hours in stats (example: '+02:00')",
+        default = "UtcOffset(0)"
+    )]
+    utc_offset: UtcOffset,
+    #[argh(
+        option,
+        description = "print the reply and mention graph of a chat in Graphviz DOT format and exit"
+    )]
+    export_dot: Option<mfj::TelegramChatId>,
     #[argh(switch, short = 'v', description = "log more information")]
     verbose: bool,
     #[argh(positional)]
@@ -71,14 +126,26 @@ fn main() -> Result<()> {
     };
 
     let keywords = match env::var("MFJ_KEYWORDS") {
-        Ok(var) => var.split(',').map(String::from).collect(),
-        Err(env::VarError::NotPresent) => vec![String::from("kesko")],
+        Ok(var) => mfj::keywords::parse_list(&var).context("Invalid MFJ_KEYWORDS")?,
+        Err(env::VarError::NotPresent) => mfj::keywords::parse_list("kesko")?,
         Err(e) => return Err(e).context("Failed to read environment"),
     };
 
     let args: MfjOptions = argh::from_env();
 
-    if let Some(token) = args.bot_api_token.as_ref().or_else(|| var_token.as_ref()) {
+    if let Some(chat_id) = args.export_dot {
+        let metadata_store = find_dumps()?
+            .iter()
+            .find_map(|read_path| MetadataStore::open_read_only(read_path).ok())
+            .context("No loadable database file found")?;
+        print!(
+            "{}",
+            mfj::commands::command_interactions::render_dot(chat_id, &metadata_store)
+        );
+        return Ok(());
+    }
+
+    if let Some(token) = args.bot_api_token.as_ref().or(var_token.as_ref()) {
         let api_url = format!("https://api.telegram.org/bot{}", token);
         let default_filename = format!("./messages-{}.json.gz", chrono::Local::now());
 
@@ -118,11 +185,27 @@ fn main() -> Result<()> {
         })
         .context("Failed to set ctrl-c handler")?;
 
+        let keyword_options = mfj::keywords::Options {
+            ignore_diacritics: args.ignore_diacritics,
+            max_points_per_message: args.keyword_max_per_message,
+            cooldown: args.keyword_cooldown.map(|d| d.0),
+            daily_cap: args.keyword_daily_cap,
+            log_rejected: args.log_rejected_keywords,
+            ignore_forwarded: args.ignore_forwarded_keywords,
+            ignore_bots: args.ignore_bot_keywords,
+        };
+
         mfj::StatsBot::new(
             &api_url,
             Duration::from_secs(args.poll_timeout),
             metadata_store,
             keywords,
+            keyword_options,
+            args.utc_offset.0,
+            mfj::CountingOptions {
+                ignore_bots: args.ignore_bots,
+                count_albums_once: args.count_albums_once,
+            },
         )
         .poll(running)
         .with_context(|| {
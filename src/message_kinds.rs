@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A kind of message. A message can be of several kinds, such as a forwarded photo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    Text,
+    Photo,
+    Video,
+    Sticker,
+    Voice,
+    Gif,
+    Poll,
+    Link,
+    Forwarded,
+}
+
+impl MessageKind {
+    pub const ALL: [MessageKind; 9] = [
+        Self::Text,
+        Self::Photo,
+        Self::Video,
+        Self::Sticker,
+        Self::Voice,
+        Self::Gif,
+        Self::Poll,
+        Self::Link,
+        Self::Forwarded,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Text => "teksti",
+            Self::Photo => "kuva",
+            Self::Video => "video",
+            Self::Sticker => "tarra",
+            Self::Voice => "ääniviesti",
+            Self::Gif => "GIF",
+            Self::Poll => "kysely",
+            Self::Link => "linkki",
+            Self::Forwarded => "välitetty",
+        }
+    }
+}
+
+/// Classifies a Telegram message without looking at its content.
+pub fn message_kinds(message: &serde_json::Value) -> Vec<MessageKind> {
+    let has = |field: &str| message.get(field).is_some();
+    let has_link = ["entities", "caption_entities"].iter().any(|field| {
+        message[*field].as_array().is_some_and(|entities| {
+            entities
+                .iter()
+                .any(|e| matches!(e["type"].as_str(), Some("url" | "text_link")))
+        })
+    });
+
+    [
+        (MessageKind::Text, has("text")),
+        (MessageKind::Photo, has("photo")),
+        (MessageKind::Video, has("video") || has("video_note")),
+        (MessageKind::Sticker, has("sticker")),
+        (MessageKind::Voice, has("voice")),
+        (MessageKind::Gif, has("animation")),
+        (MessageKind::Poll, has("poll")),
+        (MessageKind::Link, has_link),
+        (
+            MessageKind::Forwarded,
+            has("forward_origin") || has("forward_date"),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(kind, is)| is.then_some(kind))
+    .collect()
+}
+
+/// Returns the emoji in a text, each in its fully qualified form.
+pub fn emojis(text: &str) -> impl Iterator<Item = &'static str> + '_ {
+    text.graphemes(true)
+        .filter_map(|grapheme| emojis::get(grapheme).map(|emoji| emoji.as_str()))
+}
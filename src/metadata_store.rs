@@ -1,10 +1,17 @@
-use crate::{TelegramChatId, TelegramUserId};
+use crate::{
+    commands::time_range,
+    keywords::{Acknowledgement, Keyword},
+    message_kinds::MessageKind,
+    records::{self, ChatRecords, Session},
+    TelegramChatId, TelegramMessageId, TelegramThreadId, TelegramUserId,
+};
 use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::{File, OpenOptions},
     io::{Seek, SeekFrom},
+    ops::Range,
     path::Path,
     time::{Duration, Instant},
 };
@@ -19,21 +26,226 @@ pub enum Error {
 }
 
 type ChatUserMap<T> = HashMap<TelegramChatId, HashMap<TelegramUserId, T>>;
+type TopicUserMap<T> =
+    HashMap<TelegramChatId, HashMap<TelegramThreadId, HashMap<TelegramUserId, T>>>;
+
+/// Returns the users of a whole chat, or of one of its topics.
+fn scoped_users<'a, T>(
+    chat_map: &'a ChatUserMap<T>,
+    topic_map: &'a TopicUserMap<T>,
+    chat_id: TelegramChatId,
+    thread_id: Option<TelegramThreadId>,
+) -> Option<&'a HashMap<TelegramUserId, T>> {
+    match thread_id {
+        Some(thread_id) => topic_map.get(&chat_id)?.get(&thread_id),
+        None => chat_map.get(&chat_id),
+    }
+}
+
+/// How long scored keywords are remembered per message, to adjust points when the message
+/// is edited. Edits to older messages are ignored.
+const MESSAGE_KEYWORDS_RETENTION_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// How long message authors are kept for attributing reactions
+const MESSAGE_AUTHORS_RETENTION_SECS: i64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MessageAuthor {
+    user_id: TelegramUserId,
+    timestamp: i64,
+    /// Last known total of anonymous reactions
+    #[serde(default)]
+    anonymous_reactions: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MessageKeywords {
+    timestamp: i64,
+    keywords: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatSettings {
+    #[serde(default)]
+    pub keyword_acknowledgement: Acknowledgement,
+    #[serde(default)]
+    pub keyword_template: Option<String>,
+    #[serde(default)]
+    pub keyword_reaction: Option<String>,
+}
+
+/// A keyword hit, serialized as a plain timestamp when worth one point.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(untagged)]
+enum KeywordHit {
+    Point(i64),
+    Weighted(i64, i64),
+}
+
+impl KeywordHit {
+    fn new(timestamp: i64, points: i64) -> Self {
+        if points == 1 {
+            Self::Point(timestamp)
+        } else {
+            Self::Weighted(timestamp, points)
+        }
+    }
+
+    fn timestamp(&self) -> i64 {
+        match self {
+            Self::Point(timestamp) | Self::Weighted(timestamp, _) => *timestamp,
+        }
+    }
+
+    fn points(&self) -> i64 {
+        match self {
+            Self::Point(_) => 1,
+            Self::Weighted(_, points) => *points,
+        }
+    }
+}
+
+/// Counts of one user replying to or mentioning another.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Interactions {
+    #[serde(default)]
+    pub replies: u64,
+    #[serde(default)]
+    pub mentions: u64,
+}
+
+impl Interactions {
+    pub fn total(&self) -> u64 {
+        self.replies + self.mentions
+    }
+}
+
+/// Length totals of a user's text messages and captions.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TextTotals {
+    #[serde(default)]
+    pub messages: u64,
+    #[serde(default)]
+    pub characters: u64,
+    #[serde(default)]
+    pub words: u64,
+}
+
+impl TextTotals {
+    pub fn average_characters(&self) -> f64 {
+        self.characters as f64 / self.messages.max(1) as f64
+    }
+
+    pub fn average_words(&self) -> f64 {
+        self.words as f64 / self.messages.max(1) as f64
+    }
+}
+
+/// A user joining or leaving a chat.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MembershipEvent {
+    pub user_id: TelegramUserId,
+    pub timestamp: i64,
+    pub joined: bool,
+}
+
+/// Reactions given and received by a user.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Reactions {
+    #[serde(default)]
+    pub given: u64,
+    #[serde(default)]
+    pub received: u64,
+}
+
+#[derive(Debug)]
+pub struct KeywordTotal<'a> {
+    pub keyword: &'a str,
+    pub total: i64,
+    /// The user with the largest score by magnitude and their score
+    pub leader: Option<(TelegramUserId, i64)>,
+}
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct MetadataContent {
     #[serde(default)]
     timestamps_by_chat_user: ChatUserMap<Vec<i64>>,
+    /// Message timestamps of forum topics, also included in `timestamps_by_chat_user`
+    #[serde(default)]
+    topic_timestamps_by_chat_thread_user: TopicUserMap<Vec<i64>>,
+    #[serde(default)]
+    topic_names_by_chat: HashMap<TelegramChatId, HashMap<TelegramThreadId, String>>,
+    #[serde(default)]
+    keyword_scores_by_keyword_chat_user: HashMap<String, ChatUserMap<i64>>,
+    /// Timestamped keyword hits. Hits scored before these were recorded are only
+    /// included in the totals of `keyword_scores_by_keyword_chat_user`.
     #[serde(default)]
-    keyword_scores_by_keyword_chat_user: HashMap<String, ChatUserMap<u64>>,
+    keyword_timestamps_by_keyword_chat_user: HashMap<String, ChatUserMap<Vec<KeywordHit>>>,
     #[serde(default)]
     user_names: HashMap<TelegramUserId, String>,
+    /// Names of each user with the time they were first seen, oldest first. The time is
+    /// unknown for names seen before the history was kept.
+    #[serde(default)]
+    user_name_history: HashMap<TelegramUserId, Vec<(Option<i64>, String)>>,
+    /// Lowercase Telegram usernames, for looking up users by @mentions
+    #[serde(default)]
+    user_ids_by_username: HashMap<String, TelegramUserId>,
+    #[serde(default)]
+    keywords_by_chat: HashMap<TelegramChatId, Vec<Keyword>>,
+    #[serde(default)]
+    chat_settings: HashMap<TelegramChatId, ChatSettings>,
+    /// Interactions from a user (outer key) to other users (inner key)
+    #[serde(default)]
+    interactions_by_chat_user: ChatUserMap<HashMap<TelegramUserId, Interactions>>,
+    #[serde(default)]
+    message_kind_counts_by_chat_user: ChatUserMap<HashMap<MessageKind, u64>>,
+    #[serde(default)]
+    topic_message_kind_counts_by_chat_thread_user: TopicUserMap<HashMap<MessageKind, u64>>,
+    #[serde(default)]
+    text_totals_by_chat_user: ChatUserMap<TextTotals>,
+    #[serde(default)]
+    topic_text_totals_by_chat_thread_user: TopicUserMap<TextTotals>,
+    /// Emoji in texts and stickers
+    #[serde(default)]
+    emoji_counts_by_chat_user: ChatUserMap<HashMap<String, u64>>,
+    #[serde(default)]
+    sticker_set_counts_by_chat_user: ChatUserMap<HashMap<String, u64>>,
+    #[serde(default)]
+    membership_events_by_chat: HashMap<TelegramChatId, Vec<MembershipEvent>>,
+    #[serde(default)]
+    reactions_by_chat_user: ChatUserMap<Reactions>,
+    #[serde(default)]
+    authors_by_chat_message: HashMap<TelegramChatId, HashMap<TelegramMessageId, MessageAuthor>>,
+    #[serde(default)]
+    keywords_by_chat_message: HashMap<TelegramChatId, HashMap<TelegramMessageId, MessageKeywords>>,
+}
+
+/// Counts timestamps within `range` by user, sorted by count.
+fn count_by_user(
+    users_timestamps: Option<&HashMap<TelegramUserId, Vec<i64>>>,
+    range: Range<i64>,
+) -> Vec<(TelegramUserId, usize)> {
+    let mut result: Vec<(TelegramUserId, usize)> = users_timestamps
+        .into_iter()
+        .flatten()
+        .map(|(u, t)| (*u, t.iter().filter(|t| range.contains(t)).count()))
+        .filter(|(_, n)| *n > 0)
+        .collect();
+    result.sort_unstable_by_key(|(user_id, count)| (std::cmp::Reverse(*count), *user_id));
+    result
 }
 
 #[derive(Debug)]
 pub struct MetadataStore {
     content: MetadataContent,
-    file: File,
+    /// Derived from `content` on load and kept up to date as messages are added
+    records_by_chat: HashMap<TelegramChatId, ChatRecords>,
+    /// User names of each chat with the user id added to names shared by several users in
+    /// the chat, derived from `content.user_names`
+    display_names_by_chat: HashMap<TelegramChatId, HashMap<TelegramUserId, String>>,
+    utc_offset: i32,
+    /// `None` when opened read-only
+    file: Option<File>,
     last_written: Instant,
     write_interval: Duration,
 }
@@ -51,8 +263,7 @@ impl MetadataStore {
         );
 
         let content = if let Some(read_path) = read_path {
-            let read_file = File::open(&read_path)?;
-            serde_json::from_reader(GzDecoder::new(&read_file))?
+            Self::read_content(read_path)?
         } else {
             Default::default()
         };
@@ -60,19 +271,163 @@ impl MetadataStore {
         let write_file = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(false)
             .open(&write_path)?;
 
-        Ok(Self {
+        Ok(Self::with_content(
+            content,
+            Some(write_file),
+            write_interval,
+        ))
+    }
+
+    /// Keeps everything in memory, for tests.
+    #[cfg(test)]
+    pub(crate) fn in_memory() -> Self {
+        Self::with_content(Default::default(), None, Duration::MAX)
+    }
+
+    /// Opens a file for queries only, changes are never written.
+    pub fn open_read_only(read_path: impl AsRef<Path>) -> Result<Self, Error> {
+        let content = Self::read_content(read_path)?;
+        Ok(Self::with_content(content, None, Duration::MAX))
+    }
+
+    fn read_content(read_path: impl AsRef<Path>) -> Result<MetadataContent, Error> {
+        let read_file = File::open(&read_path)?;
+        Ok(serde_json::from_reader(GzDecoder::new(&read_file))?)
+    }
+
+    fn with_content(
+        mut content: MetadataContent,
+        file: Option<File>,
+        write_interval: Duration,
+    ) -> Self {
+        for (user_id, name) in &content.user_names {
+            content
+                .user_name_history
+                .entry(*user_id)
+                .or_insert_with(|| vec![(None, name.clone())]);
+        }
+        let mut metadata_store = Self {
             content,
-            file: write_file,
+            records_by_chat: HashMap::new(),
+            display_names_by_chat: HashMap::new(),
+            utc_offset: 0,
+            file,
             last_written: Instant::now(),
             write_interval,
-        })
+        };
+        metadata_store.rebuild_records();
+        metadata_store.rebuild_display_names();
+        metadata_store
+    }
+
+    /// Sets the UTC offset used for calendar days in records.
+    pub fn set_utc_offset(&mut self, utc_offset: i32) {
+        if utc_offset != self.utc_offset {
+            self.utc_offset = utc_offset;
+            self.rebuild_records();
+        }
+    }
+
+    fn rebuild_records(&mut self) {
+        self.records_by_chat.clear();
+        for (chat_id, users_timestamps) in &self.content.timestamps_by_chat_user {
+            let mut messages: Vec<(i64, TelegramUserId)> = users_timestamps
+                .iter()
+                .flat_map(|(u, t)| t.iter().map(move |t| (*t, *u)))
+                .collect();
+            messages.sort_unstable();
+
+            let records = self.records_by_chat.entry(*chat_id).or_default();
+            for (timestamp, user_id) in messages {
+                records.add(user_id, timestamp, self.utc_offset);
+            }
+        }
+    }
+
+    fn rebuild_display_names(&mut self) {
+        let chat_ids: HashSet<TelegramChatId> = self
+            .content
+            .timestamps_by_chat_user
+            .keys()
+            .chain(self.content.membership_events_by_chat.keys())
+            .chain(self.content.interactions_by_chat_user.keys())
+            .copied()
+            .collect();
+        self.display_names_by_chat = chat_ids
+            .into_iter()
+            .map(|chat_id| (chat_id, self.chat_display_names(chat_id)))
+            .collect();
+    }
+
+    /// Users seen in a chat as senders, members or in interactions.
+    fn chat_user_ids(&self, chat_id: TelegramChatId) -> HashSet<TelegramUserId> {
+        let content = &self.content;
+        let senders = content.timestamps_by_chat_user.get(&chat_id);
+        let members = content.membership_events_by_chat.get(&chat_id);
+        let interactions = content.interactions_by_chat_user.get(&chat_id);
+        senders
+            .into_iter()
+            .flat_map(HashMap::keys)
+            .copied()
+            .chain(members.into_iter().flatten().map(|e| e.user_id))
+            .chain(
+                interactions
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|(from, to)| std::iter::once(*from).chain(to.keys().copied())),
+            )
+            .collect()
+    }
+
+    /// Returns true if the user has been seen in the chat.
+    pub fn is_chat_user(&self, chat_id: TelegramChatId, user_id: TelegramUserId) -> bool {
+        self.chat_user_ids(chat_id).contains(&user_id)
+    }
+
+    fn chat_display_names(&self, chat_id: TelegramChatId) -> HashMap<TelegramUserId, String> {
+        let names: Vec<(TelegramUserId, &String)> = self
+            .chat_user_ids(chat_id)
+            .into_iter()
+            .filter_map(|user_id| Some((user_id, self.content.user_names.get(&user_id)?)))
+            .collect();
+        let mut user_counts_by_name: HashMap<&str, usize> = HashMap::new();
+        for (_, name) in &names {
+            *user_counts_by_name.entry(name).or_default() += 1;
+        }
+
+        // Names of users with a username include it, so only users without one can share a name
+        names
+            .into_iter()
+            .map(|(user_id, name)| {
+                let display_name = if user_counts_by_name[name.as_str()] == 1 {
+                    name.clone()
+                } else {
+                    format!("{} #{}", name, user_id)
+                };
+                (user_id, display_name)
+            })
+            .collect()
+    }
+
+    /// Updates the display names of a chat when a named user is first seen in it.
+    fn add_chat_user(&mut self, chat_id: TelegramChatId, user_id: TelegramUserId) {
+        let known = self
+            .display_names_by_chat
+            .get(&chat_id)
+            .is_some_and(|names| names.contains_key(&user_id));
+        if !known && self.content.user_names.contains_key(&user_id) {
+            let names = self.chat_display_names(chat_id);
+            self.display_names_by_chat.insert(chat_id, names);
+        }
     }
 
     pub fn add_message(
         &mut self,
         chat_id: TelegramChatId,
+        thread_id: Option<TelegramThreadId>,
         user_id: TelegramUserId,
         timestamp: i64,
     ) -> Result<(), Error> {
@@ -80,77 +435,738 @@ impl MetadataStore {
             .content
             .timestamps_by_chat_user
             .entry(chat_id)
-            .or_insert_with(HashMap::new);
-        users_timestamps
+            .or_default();
+        users_timestamps.entry(user_id).or_default().push(timestamp);
+        self.add_chat_user(chat_id, user_id);
+        if let Some(thread_id) = thread_id {
+            self.content
+                .topic_timestamps_by_chat_thread_user
+                .entry(chat_id)
+                .or_default()
+                .entry(thread_id)
+                .or_default()
+                .entry(user_id)
+                .or_default()
+                .push(timestamp);
+        }
+        self.records_by_chat
+            .entry(chat_id)
+            .or_default()
+            .add(user_id, timestamp, self.utc_offset);
+
+        self.sync_file_if_due()
+    }
+
+    pub fn add_message_kinds(
+        &mut self,
+        chat_id: TelegramChatId,
+        thread_id: Option<TelegramThreadId>,
+        user_id: TelegramUserId,
+        kinds: &[MessageKind],
+    ) -> Result<(), Error> {
+        let add = |counts: &mut HashMap<MessageKind, u64>| {
+            for kind in kinds {
+                *counts.entry(*kind).or_default() += 1;
+            }
+        };
+        add(self
+            .content
+            .message_kind_counts_by_chat_user
+            .entry(chat_id)
+            .or_default()
             .entry(user_id)
-            .or_insert_with(Vec::new)
-            .push(timestamp);
+            .or_default());
+        if let Some(thread_id) = thread_id {
+            add(self
+                .content
+                .topic_message_kind_counts_by_chat_thread_user
+                .entry(chat_id)
+                .or_default()
+                .entry(thread_id)
+                .or_default()
+                .entry(user_id)
+                .or_default());
+        }
 
-        if self.last_written.elapsed() > self.write_interval {
-            self.sync_file()?;
-            self.last_written = Instant::now();
+        self.sync_file_if_due()
+    }
+
+    /// Returns the message kind counts of each user in a chat or one of its topics.
+    pub fn get_message_kind_counts(
+        &self,
+        chat_id: TelegramChatId,
+        thread_id: Option<TelegramThreadId>,
+    ) -> Vec<(TelegramUserId, &HashMap<MessageKind, u64>)> {
+        scoped_users(
+            &self.content.message_kind_counts_by_chat_user,
+            &self.content.topic_message_kind_counts_by_chat_thread_user,
+            chat_id,
+            thread_id,
+        )
+        .into_iter()
+        .flatten()
+        .map(|(user_id, counts)| (*user_id, counts))
+        .collect()
+    }
+
+    /// Adds the length of a message text to the user's totals. The text itself is not stored.
+    pub fn add_text_length(
+        &mut self,
+        chat_id: TelegramChatId,
+        thread_id: Option<TelegramThreadId>,
+        user_id: TelegramUserId,
+        text: &str,
+    ) -> Result<(), Error> {
+        let characters = text.chars().count() as u64;
+        let words = text.split_whitespace().count() as u64;
+        let add = |totals: &mut TextTotals| {
+            totals.messages += 1;
+            totals.characters += characters;
+            totals.words += words;
+        };
+        add(self
+            .content
+            .text_totals_by_chat_user
+            .entry(chat_id)
+            .or_default()
+            .entry(user_id)
+            .or_default());
+        if let Some(thread_id) = thread_id {
+            add(self
+                .content
+                .topic_text_totals_by_chat_thread_user
+                .entry(chat_id)
+                .or_default()
+                .entry(thread_id)
+                .or_default()
+                .entry(user_id)
+                .or_default());
         }
-        Ok(())
+
+        self.sync_file_if_due()
+    }
+
+    pub fn get_text_totals(
+        &self,
+        chat_id: TelegramChatId,
+        user_id: TelegramUserId,
+    ) -> Option<TextTotals> {
+        self.content
+            .text_totals_by_chat_user
+            .get(&chat_id)?
+            .get(&user_id)
+            .copied()
+    }
+
+    /// Returns the text totals of each user in a chat or one of its topics, sorted by average
+    /// length.
+    pub fn get_text_totals_by_user(
+        &self,
+        chat_id: TelegramChatId,
+        thread_id: Option<TelegramThreadId>,
+    ) -> Vec<(TelegramUserId, TextTotals)> {
+        let mut result: Vec<(TelegramUserId, TextTotals)> = scoped_users(
+            &self.content.text_totals_by_chat_user,
+            &self.content.topic_text_totals_by_chat_thread_user,
+            chat_id,
+            thread_id,
+        )
+        .into_iter()
+        .flatten()
+        .filter(|(_, totals)| totals.messages > 0)
+        .map(|(user_id, totals)| (*user_id, *totals))
+        .collect();
+        result.sort_unstable_by(|a, b| {
+            b.1.average_characters()
+                .total_cmp(&a.1.average_characters())
+                .then(a.0.cmp(&b.0))
+        });
+        result
+    }
+
+    pub fn add_emoji(
+        &mut self,
+        chat_id: TelegramChatId,
+        user_id: TelegramUserId,
+        emoji: &str,
+    ) -> Result<(), Error> {
+        Self::increment(
+            &mut self.content.emoji_counts_by_chat_user,
+            chat_id,
+            user_id,
+            emoji,
+        );
+
+        self.sync_file_if_due()
+    }
+
+    pub fn add_sticker_set(
+        &mut self,
+        chat_id: TelegramChatId,
+        user_id: TelegramUserId,
+        set_name: &str,
+    ) -> Result<(), Error> {
+        Self::increment(
+            &mut self.content.sticker_set_counts_by_chat_user,
+            chat_id,
+            user_id,
+            set_name,
+        );
+
+        self.sync_file_if_due()
+    }
+
+    fn increment(
+        counts: &mut ChatUserMap<HashMap<String, u64>>,
+        chat_id: TelegramChatId,
+        user_id: TelegramUserId,
+        key: &str,
+    ) {
+        let counts = counts
+            .entry(chat_id)
+            .or_default()
+            .entry(user_id)
+            .or_default();
+        match counts.get_mut(key) {
+            Some(count) => *count += 1,
+            None => {
+                counts.insert(key.into(), 1);
+            }
+        }
+    }
+
+    /// Returns the emoji counts of each user in a chat.
+    pub fn get_emoji_counts(
+        &self,
+        chat_id: TelegramChatId,
+    ) -> Vec<(TelegramUserId, &HashMap<String, u64>)> {
+        Self::user_counts(&self.content.emoji_counts_by_chat_user, chat_id)
+    }
+
+    /// Returns the sticker set counts of each user in a chat.
+    pub fn get_sticker_set_counts(
+        &self,
+        chat_id: TelegramChatId,
+    ) -> Vec<(TelegramUserId, &HashMap<String, u64>)> {
+        Self::user_counts(&self.content.sticker_set_counts_by_chat_user, chat_id)
+    }
+
+    fn user_counts(
+        counts: &ChatUserMap<HashMap<String, u64>>,
+        chat_id: TelegramChatId,
+    ) -> Vec<(TelegramUserId, &HashMap<String, u64>)> {
+        counts
+            .get(&chat_id)
+            .into_iter()
+            .flatten()
+            .map(|(user_id, counts)| (*user_id, counts))
+            .collect()
+    }
+
+    pub fn add_membership_event(
+        &mut self,
+        chat_id: TelegramChatId,
+        user_id: TelegramUserId,
+        timestamp: i64,
+        joined: bool,
+    ) -> Result<(), Error> {
+        self.content
+            .membership_events_by_chat
+            .entry(chat_id)
+            .or_default()
+            .push(MembershipEvent {
+                user_id,
+                timestamp,
+                joined,
+            });
+        self.add_chat_user(chat_id, user_id);
+
+        self.sync_file_if_due()
+    }
+
+    /// Returns the joins and leaves of a chat in the order they were received.
+    pub fn get_membership_events(&self, chat_id: TelegramChatId) -> &[MembershipEvent] {
+        self.content
+            .membership_events_by_chat
+            .get(&chat_id)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Remembers who sent a message, so that reactions to it can be attributed.
+    pub fn set_message_author(
+        &mut self,
+        chat_id: TelegramChatId,
+        message_id: TelegramMessageId,
+        user_id: TelegramUserId,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        let messages = self
+            .content
+            .authors_by_chat_message
+            .entry(chat_id)
+            .or_default();
+        messages.insert(
+            message_id,
+            MessageAuthor {
+                user_id,
+                timestamp,
+                anonymous_reactions: 0,
+            },
+        );
+
+        self.sync_file_if_due()
+    }
+
+    fn reactions_mut(
+        &mut self,
+        chat_id: TelegramChatId,
+        user_id: TelegramUserId,
+    ) -> &mut Reactions {
+        self.content
+            .reactions_by_chat_user
+            .entry(chat_id)
+            .or_default()
+            .entry(user_id)
+            .or_default()
+    }
+
+    /// Adds a change in the number of reactions a user has put on a message. Reactions to
+    /// one's own messages are not counted.
+    pub fn add_reactions(
+        &mut self,
+        chat_id: TelegramChatId,
+        message_id: TelegramMessageId,
+        user_id: TelegramUserId,
+        change: i64,
+    ) -> Result<(), Error> {
+        let author = self
+            .content
+            .authors_by_chat_message
+            .get(&chat_id)
+            .and_then(|messages| messages.get(&message_id))
+            .map(|m| m.user_id);
+        if author == Some(user_id) {
+            return Ok(());
+        }
+
+        let given = &mut self.reactions_mut(chat_id, user_id).given;
+        *given = given.saturating_add_signed(change);
+        if let Some(author) = author {
+            let received = &mut self.reactions_mut(chat_id, author).received;
+            *received = received.saturating_add_signed(change);
+        }
+
+        self.sync_file_if_due()
+    }
+
+    /// Updates the total of anonymous reactions on a message.
+    pub fn set_anonymous_reactions(
+        &mut self,
+        chat_id: TelegramChatId,
+        message_id: TelegramMessageId,
+        total: u64,
+    ) -> Result<(), Error> {
+        let Some(message) = self
+            .content
+            .authors_by_chat_message
+            .get_mut(&chat_id)
+            .and_then(|messages| messages.get_mut(&message_id))
+        else {
+            return Ok(());
+        };
+        let change = total as i64 - message.anonymous_reactions as i64;
+        message.anonymous_reactions = total;
+        let author = message.user_id;
+
+        let received = &mut self.reactions_mut(chat_id, author).received;
+        *received = received.saturating_add_signed(change);
+
+        self.sync_file_if_due()
+    }
+
+    /// Returns reaction counts of users sorted by reactions received.
+    pub fn get_reactions_by_user(
+        &self,
+        chat_id: TelegramChatId,
+    ) -> Vec<(TelegramUserId, Reactions)> {
+        let mut result: Vec<(TelegramUserId, Reactions)> = self
+            .content
+            .reactions_by_chat_user
+            .get(&chat_id)
+            .into_iter()
+            .flatten()
+            .filter(|(_, r)| r.given > 0 || r.received > 0)
+            .map(|(user_id, r)| (*user_id, *r))
+            .collect();
+        result.sort_unstable_by_key(|(user_id, r)| (std::cmp::Reverse(r.received), *user_id));
+        result
+    }
+
+    pub fn add_reply(
+        &mut self,
+        chat_id: TelegramChatId,
+        from_user_id: TelegramUserId,
+        to_user_id: TelegramUserId,
+    ) -> Result<(), Error> {
+        self.interactions_mut(chat_id, from_user_id, to_user_id)
+            .replies += 1;
+
+        self.sync_file_if_due()
+    }
+
+    pub fn add_mention(
+        &mut self,
+        chat_id: TelegramChatId,
+        from_user_id: TelegramUserId,
+        to_user_id: TelegramUserId,
+    ) -> Result<(), Error> {
+        self.interactions_mut(chat_id, from_user_id, to_user_id)
+            .mentions += 1;
+
+        self.sync_file_if_due()
+    }
+
+    fn interactions_mut(
+        &mut self,
+        chat_id: TelegramChatId,
+        from_user_id: TelegramUserId,
+        to_user_id: TelegramUserId,
+    ) -> &mut Interactions {
+        self.add_chat_user(chat_id, from_user_id);
+        self.add_chat_user(chat_id, to_user_id);
+        self.content
+            .interactions_by_chat_user
+            .entry(chat_id)
+            .or_default()
+            .entry(from_user_id)
+            .or_default()
+            .entry(to_user_id)
+            .or_default()
     }
 
-    pub fn add_keyword_point(
+    /// Returns all directed interactions in a chat as `(from, to, interactions)`.
+    pub fn get_interactions(
+        &self,
+        chat_id: TelegramChatId,
+    ) -> Vec<(TelegramUserId, TelegramUserId, Interactions)> {
+        self.content
+            .interactions_by_chat_user
+            .get(&chat_id)
+            .into_iter()
+            .flatten()
+            .flat_map(|(from, to)| to.iter().map(move |(to, i)| (*from, *to, *i)))
+            .collect()
+    }
+
+    pub fn add_keyword_hit(
         &mut self,
         keyword: &str,
         chat_id: TelegramChatId,
         user_id: TelegramUserId,
+        timestamp: i64,
+        points: i64,
     ) -> Result<(), Error> {
         let chat_users_scores = self
             .content
             .keyword_scores_by_keyword_chat_user
             .entry(keyword.to_string())
-            .or_insert_with(HashMap::new);
-        let users_scores = chat_users_scores
+            .or_default();
+        let users_scores = chat_users_scores.entry(chat_id).or_default();
+        *users_scores.entry(user_id).or_insert(0) += points;
+
+        self.content
+            .keyword_timestamps_by_keyword_chat_user
+            .entry(keyword.to_string())
+            .or_default()
             .entry(chat_id)
-            .or_insert_with(HashMap::new);
-        *users_scores.entry(user_id).or_insert(0) += 1;
+            .or_default()
+            .entry(user_id)
+            .or_default()
+            .push(KeywordHit::new(timestamp, points));
 
-        if self.last_written.elapsed() > self.write_interval {
-            self.sync_file()?;
-            self.last_written = Instant::now();
+        self.sync_file_if_due()
+    }
+
+    /// Removes a hit recorded at `timestamp` and the points it scored.
+    pub fn remove_keyword_hit(
+        &mut self,
+        keyword: &str,
+        chat_id: TelegramChatId,
+        user_id: TelegramUserId,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        let mut points = 1;
+        if let Some(hits) = self
+            .content
+            .keyword_timestamps_by_keyword_chat_user
+            .get_mut(keyword)
+            .and_then(|c| c.get_mut(&chat_id))
+            .and_then(|u| u.get_mut(&user_id))
+        {
+            if let Some(i) = hits.iter().rposition(|h| h.timestamp() == timestamp) {
+                points = hits.remove(i).points();
+            }
         }
-        Ok(())
+
+        if let Some(score) = self
+            .content
+            .keyword_scores_by_keyword_chat_user
+            .get_mut(keyword)
+            .and_then(|c| c.get_mut(&chat_id))
+            .and_then(|u| u.get_mut(&user_id))
+        {
+            *score -= points;
+        }
+
+        self.sync_file_if_due()
+    }
+
+    /// Returns the keywords scored from a message, empty if none or no longer tracked.
+    pub fn get_message_keywords(
+        &self,
+        chat_id: TelegramChatId,
+        message_id: TelegramMessageId,
+    ) -> &[String] {
+        self.content
+            .keywords_by_chat_message
+            .get(&chat_id)
+            .and_then(|m| m.get(&message_id))
+            .map_or(&[], |m| m.keywords.as_slice())
+    }
+
+    /// Tells whether scored keywords of a message sent at `timestamp` are still remembered.
+    pub fn is_message_keywords_tracked(&self, timestamp: i64, now: i64) -> bool {
+        now - timestamp < MESSAGE_KEYWORDS_RETENTION_SECS
+    }
+
+    pub fn set_message_keywords(
+        &mut self,
+        chat_id: TelegramChatId,
+        message_id: TelegramMessageId,
+        timestamp: i64,
+        keywords: Vec<String>,
+    ) -> Result<(), Error> {
+        let messages = self
+            .content
+            .keywords_by_chat_message
+            .entry(chat_id)
+            .or_default();
+        if keywords.is_empty() {
+            messages.remove(&message_id);
+        } else {
+            messages.insert(
+                message_id,
+                MessageKeywords {
+                    timestamp,
+                    keywords,
+                },
+            );
+        }
+
+        self.sync_file_if_due()
+    }
+
+    /// Returns the chat's own keyword list, `None` if the chat uses the default keywords.
+    pub fn get_chat_keywords(&self, chat_id: TelegramChatId) -> Option<&[Keyword]> {
+        self.content
+            .keywords_by_chat
+            .get(&chat_id)
+            .map(|k| k.as_slice())
     }
 
-    pub fn add_user_name(&mut self, user_id: TelegramUserId, name: String) {
-        self.content.user_names.insert(user_id, name);
+    pub fn set_chat_keywords(
+        &mut self,
+        chat_id: TelegramChatId,
+        keywords: Vec<Keyword>,
+    ) -> Result<(), Error> {
+        self.content.keywords_by_chat.insert(chat_id, keywords);
+
+        self.sync_file_if_due()
+    }
+
+    /// Adds a name of a user seen at `timestamp`. Names may be added out of order, the latest
+    /// one is the current name.
+    pub fn add_user_name(&mut self, user_id: TelegramUserId, name: String, timestamp: i64) {
+        let history = self.content.user_name_history.entry(user_id).or_default();
+        let position = history.partition_point(|(t, _)| *t <= Some(timestamp));
+        if position == 0 || history[position - 1].1 != name {
+            match history.get_mut(position) {
+                // Seen earlier than thought
+                Some((first_seen, next)) if *next == name => *first_seen = Some(timestamp),
+                _ => history.insert(position, (Some(timestamp), name)),
+            }
+        }
+
+        let current = &history.last().unwrap().1;
+        if self.content.user_names.get(&user_id) != Some(current) {
+            self.content.user_names.insert(user_id, current.clone());
+            self.rebuild_display_names();
+        }
+    }
+
+    /// Returns the names of a user with the time each was first seen, oldest first.
+    pub fn get_user_name_history(&self, user_id: TelegramUserId) -> &[(Option<i64>, String)] {
+        self.content
+            .user_name_history
+            .get(&user_id)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the name of a user, followed by the user id if another user in the chat has the
+    /// same name.
+    pub fn get_user_name(&self, chat_id: TelegramChatId, user_id: TelegramUserId) -> Option<&str> {
+        self.display_names_by_chat
+            .get(&chat_id)
+            .and_then(|names| names.get(&user_id))
+            .or_else(|| self.content.user_names.get(&user_id))
+            .map(String::as_str)
+    }
+
+    /// Returns the name of a user like [`Self::get_user_name`], or the user id if the name is
+    /// unknown.
+    pub fn get_user_name_or_id(&self, chat_id: TelegramChatId, user_id: TelegramUserId) -> String {
+        self.get_user_name(chat_id, user_id)
+            .map_or_else(|| user_id.to_string(), String::from)
+    }
+
+    pub fn add_username(&mut self, user_id: TelegramUserId, username: &str) {
+        self.content
+            .user_ids_by_username
+            .insert(username.to_lowercase(), user_id);
+    }
+
+    /// Finds a user by username, with or without the leading `@`.
+    pub fn find_user_by_username(&self, username: &str) -> Option<TelegramUserId> {
+        let username = username.strip_prefix('@').unwrap_or(username);
+        self.content
+            .user_ids_by_username
+            .get(&username.to_lowercase())
+            .copied()
+    }
+
+    /// Returns all message timestamps of a user in a chat, in order of arrival.
+    pub fn get_user_timestamps(&self, chat_id: TelegramChatId, user_id: TelegramUserId) -> &[i64] {
+        self.content
+            .timestamps_by_chat_user
+            .get(&chat_id)
+            .and_then(|u| u.get(&user_id))
+            .map_or(&[], |t| t.as_slice())
+    }
+
+    pub fn get_chat_records(&self, chat_id: TelegramChatId) -> Option<&ChatRecords> {
+        self.records_by_chat.get(&chat_id)
+    }
+
+    /// Returns the chat's conversation sessions within `range`, see [`records::sessions`].
+    pub fn get_sessions(
+        &self,
+        chat_id: TelegramChatId,
+        range: Range<i64>,
+        gap: i64,
+    ) -> Vec<Session> {
+        let mut messages: Vec<(i64, TelegramUserId)> = self
+            .content
+            .timestamps_by_chat_user
+            .get(&chat_id)
+            .into_iter()
+            .flatten()
+            .flat_map(|(u, t)| t.iter().map(move |t| (*t, *u)))
+            .filter(|(t, _)| range.contains(t))
+            .collect();
+        messages.sort_unstable();
+
+        records::sessions(&messages, gap)
     }
 
-    pub fn get_user_name(&self, user_id: TelegramUserId) -> Option<&str> {
-        self.content.user_names.get(&user_id).map(|s| s.as_str())
+    /// Returns all message timestamps in a chat, in no particular order.
+    pub fn get_chat_timestamps(&self, chat_id: TelegramChatId) -> impl Iterator<Item = i64> + '_ {
+        self.content
+            .timestamps_by_chat_user
+            .get(&chat_id)
+            .into_iter()
+            .flat_map(|u| u.values().flatten().copied())
     }
 
     pub fn get_message_counts_by_user(
         &self,
         chat_id: TelegramChatId,
-        after_unix: i64,
+        range: Range<i64>,
     ) -> Vec<(TelegramUserId, usize)> {
-        let mut result = Vec::new();
+        count_by_user(self.content.timestamps_by_chat_user.get(&chat_id), range)
+    }
 
-        if let Some(users_timestamps) = self.content.timestamps_by_chat_user.get(&chat_id) {
-            result = users_timestamps
-                .iter()
-                .map(|(u, t)| (*u, t.iter().filter(|t| **t > after_unix).count()))
-                .filter(|(_, n)| *n > 0)
-                .collect();
-            result.sort_unstable_by(|a, b| b.1.cmp(&a.1));
-        }
+    pub fn get_topic_message_counts_by_user(
+        &self,
+        chat_id: TelegramChatId,
+        thread_id: TelegramThreadId,
+        range: Range<i64>,
+    ) -> Vec<(TelegramUserId, usize)> {
+        count_by_user(
+            self.content
+                .topic_timestamps_by_chat_thread_user
+                .get(&chat_id)
+                .and_then(|threads| threads.get(&thread_id)),
+            range,
+        )
+    }
 
-        result
+    pub fn set_topic_name(
+        &mut self,
+        chat_id: TelegramChatId,
+        thread_id: TelegramThreadId,
+        name: &str,
+    ) -> Result<(), Error> {
+        self.content
+            .topic_names_by_chat
+            .entry(chat_id)
+            .or_default()
+            .insert(thread_id, name.into());
+
+        self.sync_file_if_due()
+    }
+
+    pub fn get_topic_name(
+        &self,
+        chat_id: TelegramChatId,
+        thread_id: TelegramThreadId,
+    ) -> Option<&str> {
+        self.content
+            .topic_names_by_chat
+            .get(&chat_id)?
+            .get(&thread_id)
+            .map(String::as_str)
     }
 
+    /// Returns scores sorted by score. With `range`, only hits within it are counted.
     pub fn get_scores_by_user(
         &self,
         keyword: &str,
         chat_id: TelegramChatId,
-    ) -> Vec<(TelegramUserId, u64)> {
-        let mut result: Vec<(TelegramUserId, u64)> = Vec::new();
+        range: Option<Range<i64>>,
+    ) -> Vec<(TelegramUserId, i64)> {
+        let mut result: Vec<(TelegramUserId, i64)> = Vec::new();
 
-        if let Some(chat_users_scores) = self
+        if let Some(range) = range {
+            if let Some(users_hits) = self
+                .content
+                .keyword_timestamps_by_keyword_chat_user
+                .get(keyword)
+                .and_then(|c| c.get(&chat_id))
+            {
+                result = users_hits
+                    .iter()
+                    .map(|(u, hits)| {
+                        let score = hits
+                            .iter()
+                            .filter(|h| range.contains(&h.timestamp()))
+                            .map(KeywordHit::points)
+                            .sum();
+                        (*u, score)
+                    })
+                    .filter(|(_, s)| *s != 0)
+                    .collect();
+            }
+        } else if let Some(chat_users_scores) = self
             .content
             .keyword_scores_by_keyword_chat_user
             .get(keyword)
@@ -158,24 +1174,129 @@ impl MetadataStore {
             if let Some(users_scores) = chat_users_scores.get(&chat_id) {
                 result = users_scores
                     .iter()
-                    .filter(|(_, &s)| s > 0)
+                    .filter(|(_, &s)| s != 0)
                     .map(|(u, s)| (*u, *s))
                     .collect();
-                result.sort_unstable_by(|a, b| b.1.cmp(&a.1));
             }
         }
+        result.sort_unstable_by_key(|(user_id, score)| (std::cmp::Reverse(*score), *user_id));
+
+        result
+    }
+
+    /// Returns the total score and the top scorer of every keyword scored in the chat,
+    /// sorted by total.
+    pub fn get_keyword_totals(&self, chat_id: TelegramChatId) -> Vec<KeywordTotal<'_>> {
+        let mut result: Vec<_> = self
+            .content
+            .keyword_scores_by_keyword_chat_user
+            .iter()
+            .filter_map(|(keyword, chat_users_scores)| {
+                let users_scores = chat_users_scores.get(&chat_id)?;
+                let total: i64 = users_scores.values().sum();
+                let leader = users_scores
+                    .iter()
+                    .filter(|(_, &s)| s != 0)
+                    .max_by_key(|(_, &s)| s.abs())
+                    .map(|(u, s)| (*u, *s));
+                Some(KeywordTotal {
+                    keyword,
+                    total,
+                    leader,
+                })
+            })
+            .filter(|t| t.total != 0)
+            .collect();
+        result.sort_unstable_by(|a, b| b.total.cmp(&a.total).then(a.keyword.cmp(b.keyword)));
+
+        result
+    }
+
+    /// Returns a user's score for every keyword in the chat, sorted by score.
+    pub fn get_user_scores_by_keyword(
+        &self,
+        chat_id: TelegramChatId,
+        user_id: TelegramUserId,
+    ) -> Vec<(&str, i64)> {
+        let mut result: Vec<_> = self
+            .content
+            .keyword_scores_by_keyword_chat_user
+            .iter()
+            .filter_map(|(keyword, chat_users_scores)| {
+                let score = *chat_users_scores.get(&chat_id)?.get(&user_id)?;
+                Some((keyword.as_str(), score))
+            })
+            .filter(|(_, s)| *s != 0)
+            .collect();
+        result.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
 
         result
     }
 
+    /// Returns the timestamps and points of all recorded hits of a keyword in a chat,
+    /// in no particular order.
+    pub fn get_keyword_hits(&self, keyword: &str, chat_id: TelegramChatId) -> Vec<(i64, i64)> {
+        self.content
+            .keyword_timestamps_by_keyword_chat_user
+            .get(keyword)
+            .and_then(|c| c.get(&chat_id))
+            .map(|u| {
+                u.values()
+                    .flatten()
+                    .map(|h| (h.timestamp(), h.points()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_chat_settings(&self, chat_id: TelegramChatId) -> ChatSettings {
+        self.content
+            .chat_settings
+            .get(&chat_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_chat_settings(
+        &mut self,
+        chat_id: TelegramChatId,
+        settings: ChatSettings,
+    ) -> Result<(), Error> {
+        self.content.chat_settings.insert(chat_id, settings);
+
+        self.sync_file_if_due()
+    }
+
+    /// Drops per-message data past its retention time.
+    fn prune_expired(&mut self, now: i64) {
+        for messages in self.content.keywords_by_chat_message.values_mut() {
+            messages.retain(|_, m| now - m.timestamp < MESSAGE_KEYWORDS_RETENTION_SECS);
+        }
+        for messages in self.content.authors_by_chat_message.values_mut() {
+            messages.retain(|_, m| now - m.timestamp < MESSAGE_AUTHORS_RETENTION_SECS);
+        }
+    }
+
+    fn sync_file_if_due(&mut self) -> Result<(), Error> {
+        if self.last_written.elapsed() > self.write_interval {
+            // Pruning only on writes keeps adding messages constant time
+            self.prune_expired(time_range::now());
+            self.sync_file()?;
+            self.last_written = Instant::now();
+        }
+        Ok(())
+    }
+
     fn sync_file(&mut self) -> Result<(), Error> {
-        log::info!("Writing to disk");
-        self.file.seek(SeekFrom::Start(0))?;
-        self.file.set_len(0)?;
-        serde_json::to_writer(
-            GzEncoder::new(&self.file, Compression::default()),
-            &self.content,
-        )?;
+        if let Some(file) = &mut self.file {
+            log::info!("Writing to disk");
+            file.seek(SeekFrom::Start(0))?;
+            file.set_len(0)?;
+            serde_json::to_writer(
+                GzEncoder::new(&*file, Compression::default()),
+                &self.content,
+            )?;
+        }
         Ok(())
     }
 }
@@ -185,3 +1306,40 @@ impl Drop for MetadataStore {
         self.sync_file().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(content: MetadataContent) -> MetadataStore {
+        MetadataStore::with_content(content, None, Duration::MAX)
+    }
+
+    #[test]
+    fn name_history_seeded_without_date() {
+        let mut content = MetadataContent::default();
+        content.user_names.insert(1, "Matti".into());
+        let mut store = store(content);
+        assert_eq!(store.get_user_name_history(1), [(None, "Matti".into())]);
+
+        store.add_user_name(1, "Matti".into(), 100);
+        store.add_user_name(1, "Matti V".into(), 200);
+        assert_eq!(
+            store.get_user_name_history(1),
+            [(None, "Matti".into()), (Some(200), "Matti V".into())]
+        );
+    }
+
+    #[test]
+    fn name_history_out_of_order() {
+        let mut store = store(MetadataContent::default());
+        store.add_user_name(1, "B".into(), 300);
+        store.add_user_name(1, "A".into(), 100);
+        store.add_user_name(1, "B".into(), 200);
+        assert_eq!(
+            store.get_user_name_history(1),
+            [(Some(100), "A".into()), (Some(200), "B".into())]
+        );
+        assert_eq!(store.get_user_name(0, 1), Some("B"));
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{commands::time_range, TelegramUserId};
+use std::collections::{HashMap, HashSet};
+
+/// Consecutive days with at least one message.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Streak {
+    pub last_day: i64,
+    pub current: u32,
+    pub longest: u32,
+    /// The last day of the longest streak
+    pub longest_end_day: i64,
+}
+
+impl Streak {
+    /// Adds a day with messages. Days are expected in order, earlier days are ignored.
+    pub fn add(&mut self, day: i64) {
+        if self.current > 0 && day <= self.last_day {
+            return;
+        }
+        self.current = if self.current > 0 && day == self.last_day + 1 {
+            self.current + 1
+        } else {
+            1
+        };
+        self.last_day = day;
+        if self.current >= self.longest {
+            self.longest = self.current;
+            self.longest_end_day = day;
+        }
+    }
+
+    /// Tells whether the streak can still continue, that is the user has sent a message
+    /// today or yesterday.
+    pub fn is_ongoing(&self, today: i64) -> bool {
+        self.current > 0 && self.last_day >= today - 1
+    }
+}
+
+/// Chat records, updated one message at a time.
+#[derive(Debug, Clone, Default)]
+pub struct ChatRecords {
+    pub message_counts_by_day: HashMap<i64, usize>,
+    pub last_timestamp: Option<i64>,
+    /// Start and end timestamps of the longest gap between messages
+    pub longest_silence: Option<(i64, i64)>,
+    pub streaks_by_user: HashMap<TelegramUserId, Streak>,
+}
+
+impl ChatRecords {
+    /// Adds a message. Messages are expected in chronological order, older messages only
+    /// count towards daily totals.
+    pub fn add(&mut self, user_id: TelegramUserId, timestamp: i64, utc_offset: i32) {
+        let day = time_range::local_day(timestamp, utc_offset);
+        *self.message_counts_by_day.entry(day).or_insert(0) += 1;
+
+        if let Some(last) = self.last_timestamp {
+            if timestamp < last {
+                return;
+            }
+            let longest = self.longest_silence.map_or(0, |(start, end)| end - start);
+            if timestamp - last > longest {
+                self.longest_silence = Some((last, timestamp));
+            }
+        }
+        self.last_timestamp = Some(timestamp);
+
+        self.streaks_by_user.entry(user_id).or_default().add(day);
+    }
+
+    /// Returns the day number and message count of the busiest day.
+    pub fn busiest_day(&self) -> Option<(i64, usize)> {
+        self.message_counts_by_day
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            .map(|(day, count)| (*day, *count))
+    }
+}
+
+/// A run of messages without gaps longer than the session gap.
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// The user who sent the first message
+    pub starter: TelegramUserId,
+    /// Whether the session follows an earlier one, so that the starter broke a silence.
+    /// The first session has no known silence before it.
+    pub breaks_silence: bool,
+    pub start: i64,
+    pub end: i64,
+    pub message_count: usize,
+    pub participants: HashSet<TelegramUserId>,
+}
+
+/// Splits chronologically sorted messages into sessions separated by more than `gap` seconds.
+pub fn sessions(messages: &[(i64, TelegramUserId)], gap: i64) -> Vec<Session> {
+    let mut sessions: Vec<Session> = Vec::new();
+    for (timestamp, user_id) in messages {
+        match sessions.last_mut() {
+            Some(session) if timestamp - session.end <= gap => {
+                session.end = *timestamp;
+                session.message_count += 1;
+                session.participants.insert(*user_id);
+            }
+            _ => sessions.push(Session {
+                starter: *user_id,
+                breaks_silence: !sessions.is_empty(),
+                start: *timestamp,
+                end: *timestamp,
+                message_count: 1,
+                participants: HashSet::from([*user_id]),
+            }),
+        }
+    }
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_session_does_not_break_silence() {
+        let sessions = sessions(&[(0, 1), (100, 2), (1000, 2)], 300);
+        assert_eq!(sessions.len(), 2);
+        assert!(!sessions[0].breaks_silence);
+        assert_eq!(sessions[0].participants, HashSet::from([1, 2]));
+        assert!(sessions[1].breaks_silence);
+        assert_eq!(sessions[1].starter, 2);
+    }
+
+    #[test]
+    fn session_gap_is_inclusive() {
+        assert_eq!(sessions(&[(0, 1), (300, 1)], 300).len(), 1);
+        assert_eq!(sessions(&[(0, 1), (301, 1)], 300).len(), 2);
+    }
+
+    #[test]
+    fn older_messages_only_count_towards_days() {
+        let mut records = ChatRecords::default();
+        records.add(1, 1000, 0);
+        records.add(1, 5000, 0);
+        records.add(2, 100, 0);
+        assert_eq!(records.last_timestamp, Some(5000));
+        assert_eq!(records.longest_silence, Some((1000, 5000)));
+        assert_eq!(records.message_counts_by_day[&0], 3);
+        assert!(!records.streaks_by_user.contains_key(&2));
+    }
+
+    #[test]
+    fn streak_resets_after_a_missed_day() {
+        let mut streak = Streak::default();
+        for day in [10, 11, 11, 12, 14, 15] {
+            streak.add(day);
+        }
+        assert_eq!(streak.current, 2);
+        assert_eq!(streak.longest, 3);
+        assert_eq!(streak.longest_end_day, 12);
+        assert!(streak.is_ongoing(16));
+        assert!(!streak.is_ongoing(17));
+    }
+}